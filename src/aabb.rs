@@ -77,6 +77,23 @@ impl AABB {
         if x_size > y_size && x_size > z_size { 0 } else if y_size > z_size { 1 } else { 2 }
     }
 
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            0.5 * (self.x.min + self.x.max),
+            0.5 * (self.y.min + self.y.max),
+            0.5 * (self.z.min + self.z.max),
+        )
+    }
+
+    // Zero for an empty box
+    pub fn surface_area(&self) -> f64 {
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        if dx < 0.0 || dy < 0.0 || dz < 0.0 {
+            return 0.0;
+        }
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
     // Optimized hit function: Precomputed inverse + Unrolled loop + ORIGINAL LOGIC
     pub fn hit(&self, r: &Ray, mut ray_t: Interval) -> bool {
         let ray_orig = r.origin();
@@ -1,15 +1,62 @@
 // bvh.rs
 
 use std::cmp::Ordering;
+use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::hittable::{Hittable, HitRecord};
 use crate::hittable_list::{HittableList, HittablePtr};
 use crate::ray::Ray;
 use crate::interval::Interval;
 use crate::aabb::AABB;
-use crate::utils::prelude::random_i32_range;
 
+// Relative costs used by the SAH: one node visit (box test) vs one primitive hit test
+pub(crate) const TRAVERSAL_COST: f64 = 0.125;
+pub(crate) const INTERSECTION_COST: f64 = 1.0;
+
+// How a node's objects are divided between its two children
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SplitMethod {
+    // Sort along the longest axis and split at the middle object
+    #[default]
+    Median,
+    // Binned surface area heuristic: try `bins - 1` planes per axis, keep the cheapest
+    Sah { bins: usize },
+}
+
+impl SplitMethod {
+    pub const SAH: SplitMethod = SplitMethod::Sah { bins: 16 };
+}
+
+// Build time and shape of a finished tree
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BuildStats {
+    pub build_time: Duration,
+    pub primitives: usize,
+    pub nodes: usize,
+    pub leaves: usize,     // nodes whose children are the objects themselves
+    pub max_depth: usize,  // the root is depth 1
+    pub sah_cost: f64,     // expected cost of a ray through the root, in primitive hit tests
+}
+
+impl fmt::Display for BuildStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} primitives, {} nodes, {} leaves, depth {}, SAH cost {:.2}, built in {:.1} ms",
+            self.primitives,
+            self.nodes,
+            self.leaves,
+            self.max_depth,
+            self.sah_cost,
+            self.build_time.as_secs_f64() * 1000.0,
+        )
+    }
+}
+
+// The book's tree: one node per split, always at the median object along the longest axis.
+// LinearBVH is the faster flattened version and the one that offers the SAH.
 pub struct BVHNode {
     left: HittablePtr,
     right: HittablePtr,
@@ -17,9 +64,14 @@ pub struct BVHNode {
 }
 
 impl BVHNode {
-    // Build BVH from an existing list (consumes nothing; clones Arcs)
+    // Build BVH from an existing list (consumes nothing; clones Arcs). An empty list gives an
+    // empty node that nothing hits.
     pub fn new(list: &HittableList) -> Self {
         let mut objects = list.objects().clone(); // clones Arcs (cheap)
+        if objects.is_empty() {
+            let empty: HittablePtr = Arc::new(HittableList::new());
+            return Self { left: empty.clone(), right: empty, bbox: AABB::empty() };
+        }
         let end = objects.len();
         Self::new_interval(&mut objects, 0, end)
     }
@@ -28,23 +80,12 @@ impl BVHNode {
 
         let mut bbox = AABB::empty();
 
-        for i in start..end {
-            bbox = AABB::from_two_boxes(bbox, objects[i].bounding_box());
+        for object in &objects[start..end] {
+            bbox = AABB::from_two_boxes(bbox, object.bounding_box());
         }
 
-        let axis = bbox.longest_axis();
-
         let object_span = end - start;
 
-        // Sort the sub-slice in-place by bbox min along chosen axis
-        objects[start..end].sort_by(|a, b| {
-            let a_min = a.bounding_box().axis_interval(axis).min;
-            let b_min = b.bounding_box().axis_interval(axis).min;
-            a_min
-                .partial_cmp(&b_min)
-                .unwrap_or(Ordering::Equal)
-        });
-
         let (left, right): (HittablePtr, HittablePtr) = match object_span {
             1 => {
                 let a = objects[start].clone();
@@ -52,6 +93,7 @@ impl BVHNode {
             }
             2 => (objects[start].clone(), objects[start + 1].clone()),
             _ => {
+                median_sort(&mut objects[start..end], bbox.longest_axis(), &object_box);
                 let mid = start + object_span / 2;
                 let left_node = Arc::new(Self::new_interval(objects, start, mid));
                 let right_node = Arc::new(Self::new_interval(objects, mid, end));
@@ -59,12 +101,105 @@ impl BVHNode {
             }
         };
 
-        //let bbox = AABB::from_two_boxes(left.bounding_box(), right.bounding_box());
-
         Self { left, right, bbox }
     }
 }
 
+fn object_box(object: &HittablePtr) -> AABB {
+    object.bounding_box()
+}
+
+// Sort the slice in-place by bbox min along the chosen axis. The builders below work on any
+// primitive type, given its bounding box.
+pub(crate) fn median_sort<T>(objects: &mut [T], axis: i32, bbox: &impl Fn(&T) -> AABB) {
+    objects.sort_by(|a, b| {
+        let a_min = bbox(a).axis_interval(axis).min;
+        let b_min = bbox(b).axis_interval(axis).min;
+        a_min
+            .partial_cmp(&b_min)
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+// Bin the centroids along each axis and pick the plane with the lowest SAH cost.
+// On success the slice is sorted along the chosen axis and the left child gets the
+// first `count` objects. Returns (count, axis, summed area * object count of the two children).
+pub(crate) fn sah_split<T: Clone>(objects: &mut [T], bins: usize, bbox: &impl Fn(&T) -> AABB) -> Option<(usize, i32, f64)> {
+    let bins = bins.max(2);
+    let centroids: Vec<_> = objects.iter().map(|o| bbox(o).centroid()).collect();
+
+    // (axis, split after bin, cost)
+    let mut best: Option<(usize, usize, f64)> = None;
+    let mut best_range = (0.0, 0.0);
+
+    for axis in 0..3 {
+        let (lo, hi) = centroids
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), c| (lo.min(c[axis]), hi.max(c[axis])));
+        if hi - lo <= 0.0 {
+            continue;
+        }
+
+        let mut counts = vec![0usize; bins];
+        let mut boxes = vec![AABB::empty(); bins];
+        for (object, c) in objects.iter().zip(&centroids) {
+            let b = bin_index(c[axis], lo, hi, bins);
+            counts[b] += 1;
+            boxes[b] = AABB::from_two_boxes(boxes[b], bbox(object));
+        }
+
+        // Sweep from the right to get the area and count right of each plane
+        let mut right_area = vec![0.0; bins];
+        let mut right_count = vec![0usize; bins];
+        let mut acc_box = AABB::empty();
+        let mut acc_count = 0;
+        for b in (1..bins).rev() {
+            acc_box = AABB::from_two_boxes(acc_box, boxes[b]);
+            acc_count += counts[b];
+            right_area[b] = if acc_count > 0 { acc_box.surface_area() } else { 0.0 };
+            right_count[b] = acc_count;
+        }
+
+        let mut acc_box = AABB::empty();
+        let mut acc_count = 0;
+        for b in 0..bins - 1 {
+            acc_box = AABB::from_two_boxes(acc_box, boxes[b]);
+            acc_count += counts[b];
+            if acc_count == 0 || right_count[b + 1] == 0 {
+                continue;
+            }
+            let cost = acc_box.surface_area() * acc_count as f64 + right_area[b + 1] * right_count[b + 1] as f64;
+            if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                best = Some((axis, b, cost));
+                best_range = (lo, hi);
+            }
+        }
+    }
+
+    let (axis, split, cost) = best?;
+    let (lo, hi) = best_range;
+    let key = |c: f64| bin_index(c, lo, hi, bins);
+
+    let mut keyed: Vec<(usize, f64, T)> = objects
+        .iter()
+        .zip(&centroids)
+        .map(|(o, c)| (key(c[axis]), c[axis], o.clone()))
+        .collect();
+    keyed.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+
+    let count = keyed.iter().filter(|(b, _, _)| *b <= split).count();
+    for (slot, (_, _, object)) in objects.iter_mut().zip(keyed) {
+        *slot = object;
+    }
+    Some((count, axis as i32, cost))
+}
+
+#[inline]
+fn bin_index(c: f64, lo: f64, hi: f64, bins: usize) -> usize {
+    let b = ((c - lo) / (hi - lo) * bins as f64) as usize;
+    b.min(bins - 1)
+}
+
 impl Hittable for BVHNode {
     fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
         if !self.bbox.hit(r, ray_t) {
@@ -85,3 +220,35 @@ impl Hittable for BVHNode {
         self.bbox
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec3::{Color, Point3, Vec3};
+
+    #[test]
+    fn empty_node_never_hits() {
+        let bvh = BVHNode::new(&HittableList::new());
+        for _ in 0..100 {
+            let r = Ray::new(Vec3::random_range(-1.0, 1.0), Vec3::random_unit_vector());
+            assert!(!bvh.hit(&r, Interval::new(0.001, f64::INFINITY), &mut HitRecord::new()));
+        }
+    }
+
+    #[test]
+    fn finds_the_nearest_of_a_row() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::new();
+        for i in 0..7 {
+            list.add(Box::new(Sphere::new(crate::ray::point_to_ray(Point3::new(i as f64 * 3.0, 0.0, 0.0)), 1.0, material.clone())));
+        }
+        let bvh = BVHNode::new(&list);
+
+        let mut rec = HitRecord::new();
+        let r = Ray::new(Point3::new(20.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(bvh.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.t - 1.0).abs() < 1e-9, "t = {}", rec.t);
+    }
+}
@@ -4,10 +4,13 @@ use std::f64::INFINITY;
 use std::io::{self, Write};
 use crate::vec3::{Point3, Vec3, Color};
 use crate::ray::Ray;
-use crate::hittable::{Hittable, HitRecord};
+use crate::hittable::{Hittable, HitRecord, Frame};
+use crate::hittable_list::HittableList;
 use crate::interval::Interval;
 use crate::utils::prelude::{random_f64, degrees_to_radians};
 use crate::material::Material;
+use crate::medium::Medium;
+use crate::framebuffer::FrameBuffer;
 use rayon::prelude::*;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
@@ -15,7 +18,13 @@ pub struct Camera {
     pub aspect_ratio: f64, // ratio of image width over height
     pub image_width: u32, // image width in pixels
     pub samples_per_pixel: u32, // number of samples per pixel for anti-aliasing
-    pub max_depth: u32, // max recursion depth for ray tracing
+    // Hard caps on path length, overall and per kind of bounce. Light beyond them is lost, so
+    // low caps darken the image; Russian roulette ends most paths well before a generous cap
+    pub max_depth: u32, // max number of rays along a path
+    pub max_diffuse_depth: u32, // max diffuse bounces (0 = direct light only)
+    pub max_specular_depth: u32, // max mirror/glass bounces
+    pub max_volume_depth: u32, // max scattering events inside media
+    pub russian_roulette_depth: u32, // bounces before paths may be terminated at random
     pub vfov: f64, // vertical field of view in degrees
     pub sqrt_spp: i32, // square toot of number of samples per pixel
     pub recip_sqrt_spp: f64, // reciprocal of square root of samples per pixel (1/sqrt_spp)
@@ -53,6 +62,10 @@ impl Camera {
             image_height: 0,
             samples_per_pixel: 50,
             max_depth: 50,
+            max_diffuse_depth: 50,
+            max_specular_depth: 50,
+            max_volume_depth: 50,
+            russian_roulette_depth: 3,
             vfov: 90.0,
             defocus_angle: 0.0,
             focus_distance: 10.0,
@@ -77,7 +90,16 @@ impl Camera {
         }
     }
 
-    pub fn render(&mut self, world: &impl Hittable) {
+    // Render the world into an owned buffer of linear HDR colors.
+    // Writing the result out (PPM, PNG, ...) is left to the caller.
+    pub fn render(&mut self, world: &impl Hittable) -> FrameBuffer {
+        self.render_with_lights(world, &HittableList::new())
+    }
+
+    // Like render(), but also samples the given lights directly at every diffuse bounce.
+    // The lights must be part of the world as well; any emitter left out of the list is
+    // still found by chance, just with more noise.
+    pub fn render_with_lights(&mut self, world: &impl Hittable, lights: &HittableList) -> FrameBuffer {
         self.initialize();
 
         let cam = &*self; // immutable shared view for parallel work
@@ -85,6 +107,7 @@ impl Camera {
         let h = cam.image_height as usize;
 
         let rows_done = AtomicUsize::new(0);
+        let media = cam.starting_media(world);
 
         // Render rows in parallel
         let mut rows: Vec<(usize, Vec<Color>)> = (0..h)
@@ -98,7 +121,7 @@ impl Camera {
                     for s_i in 0..cam.sqrt_spp {
                         for s_j in 0..cam.sqrt_spp {
                             let r = cam.get_ray(i as i32, j as i32, s_i, s_j);
-                            pixel_color = pixel_color + cam.ray_color(&r, cam.max_depth, world);
+                            pixel_color = pixel_color + cam.ray_color(&r, world, lights, &media);
                         }
                     }
 
@@ -118,19 +141,10 @@ impl Camera {
         // Restore row order (parallel iterator returns out-of-order)
         rows.sort_by_key(|(j, _)| *j);
 
-        // Write PPM sequentially to stdout (so `> image.ppm` works)
-        let stdout = io::stdout();
-        let mut out = io::BufWriter::new(stdout.lock());
-        writeln!(out, "P3\n{} {}\n255", cam.image_width, cam.image_height).unwrap();
-
-        for (_, row) in rows {
-            for c in row {
-                Color::write_color(&mut out, c);
-            }
-        }
-
-        out.flush().unwrap();
         eprintln!("\nDone.");
+
+        let rows: Vec<Vec<Color>> = rows.into_iter().map(|(_, row)| row).collect();
+        FrameBuffer::from_rows(cam.image_width, cam.image_height, rows)
     }
 
     fn initialize(&mut self) {
@@ -213,27 +227,415 @@ impl Camera {
         return Ray::new_time(ray_origin, ray_direction, ray_time);
     }
 
-    fn ray_color(&self, r: &Ray, max_depth: u32, world: &impl Hittable) -> Color {
-        if max_depth <= 0 { return Color::init_zero(); }
+    // Path tracer: follow one path, adding up the light reached at each vertex weighted by
+    // the throughput so far. Russian roulette ends paths that carry little light without bias.
+    // `media` are the media the camera is in, innermost last.
+    fn ray_color(&self, r: &Ray, world: &impl Hittable, lights: &HittableList, media: &[PlacedMedium]) -> Color {
+        let mut radiance = Color::init_zero();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut ray = *r;
+
+        // Density with which the previous bounce picked `ray`, or None for the camera ray and
+        // specular bounces, whose emission is always counted in full
+        let mut scatter_pdf: Option<f64> = None;
+        // Where that bounce was; invisible medium boundaries restart the ray further along
+        let mut scatter_origin = ray.origin();
+
+        // The media the path is in; the last one is where it travels now
+        let mut media = media.to_vec();
+
+        let (mut diffuse_bounces, mut specular_bounces, mut volume_bounces) = (0, 0, 0);
+        let mut crossings = 0;
+
+        let mut bounce = 0;
+        while bounce < self.max_depth {
+            let mut rec = HitRecord::new();
+            let mut hit = world.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rec);
+
+            // The medium may stop the ray before it gets to the surface
+            if let Some((medium, frame)) = segment_medium(&media, &rec) {
+                let t_max = if hit { rec.t } else { f64::INFINITY };
+                let mut medium_rec = HitRecord::new();
+                let event = medium.sample(&frame.to_object(&ray), t_max, &mut medium_rec);
+                throughput = throughput * event.weight;
+                if event.scattered {
+                    medium_rec.p = ray.at(medium_rec.t); // back out of the medium's frame
+                    rec = medium_rec;
+                    hit = true;
+                }
+            }
 
-        let mut rec = HitRecord::new();
+            if !hit {
+                radiance = radiance + throughput * self.background_color;
+                break;
+            }
 
-        if !world.hit(r, Interval::new(0.001, f64::INFINITY), &mut rec) {
-            return self.background_color;
+            // Only the edge of a medium: switch media and go on, which isn't a bounce. A surface
+            // on it or right behind it (glass around smoke, a box of smoke on the floor) is hit
+            // instead, and the boundary is crossed if the path scatters through it
+            let mut boundary = None;
+            if rec.material.unwrap().is_invisible() {
+                if let Some(behind) = surface_behind(world, &ray, &rec) {
+                    boundary = Some(std::mem::replace(&mut rec, behind));
+                } else {
+                    crossings += 1;
+                    if crossings > MAX_CROSSINGS {
+                        break;
+                    }
+                    cross_boundary(&mut media, &rec, ray.direction());
+                    ray = ray.transformed(rec.p, ray.direction());
+                    continue;
+                }
+            }
+
+            let material = rec.material.unwrap();
+
+            let mut color_from_emission = material.emitted(rec.u, rec.v, &rec.p);
+
+            // A light reached by a diffuse bounce could also have been sampled directly;
+            // weight the two strategies against each other (MIS). Glowing media are never
+            // sampled as lights
+            if let Some(pdf) = scatter_pdf.filter(|_| !material.is_volume()) {
+                let light_pdf = lights.pdf_value(scatter_origin, ray.direction());
+                color_from_emission = color_from_emission * power_heuristic(pdf, light_pdf);
+            }
+            radiance = radiance + throughput * color_from_emission;
+
+            // Light sampling covers the material's non-specular lobes whichever lobe this bounce
+            // picks (a smooth coat over a diffuse base has both), and even if sampling the
+            // material fails below; purely specular materials are skipped inside
+            radiance = radiance + throughput * self.sample_lights(&ray, &rec, world, lights, &media);
+
+            let Some(srec) = material.sample(&ray, &rec) else {
+                break;
+            };
+
+            // Per-kind limits; the light sampled above still counts
+            let (count, limit) = if srec.is_specular {
+                (&mut specular_bounces, self.max_specular_depth)
+            } else if material.is_volume() {
+                (&mut volume_bounces, self.max_volume_depth)
+            } else {
+                (&mut diffuse_bounces, self.max_diffuse_depth)
+            };
+            *count += 1;
+            if *count > limit {
+                break;
+            }
+
+            throughput = throughput * srec.throughput;
+            scatter_pdf = if srec.is_specular { None } else { Some(srec.pdf) };
+            scatter_origin = rec.p;
+            cross_boundary(&mut media, &rec, srec.direction);
+            if let Some(boundary) = &boundary {
+                cross_boundary(&mut media, boundary, srec.direction);
+            }
+            ray = Ray::new_time(rec.p, srec.direction, ray.time()).with_wavelength(srec.wavelength.or(ray.wavelength()));
+
+            // Survive with probability p and divide by it, so the estimate stays unbiased
+            if bounce + 1 >= self.russian_roulette_depth {
+                let p = throughput.x().max(throughput.y()).max(throughput.z()).min(0.95);
+                if p <= 0.0 || random_f64() >= p {
+                    break;
+                }
+                throughput = throughput / p;
+            }
+            bounce += 1;
         }
 
-        let material  = rec.material.unwrap();
-        let mut scattered = Ray::new(Point3::init_zero(), Vec3::init_zero());
-        let mut attentuation = Color::init_zero();
+        radiance
+    }
+
+    // Next-event estimation: pick a point on a light, trace a shadow ray to it and weight the
+    // light it sees against the chance of the material scattering that way
+    fn sample_lights(&self, r: &Ray, rec: &HitRecord, world: &impl Hittable, lights: &HittableList, media: &[PlacedMedium]) -> Color {
+        let material = rec.material.unwrap();
+        if lights.objects().is_empty() || material.is_specular() {
+            return Color::init_zero();
+        }
+
+        let mut to_light = Ray::new_time(rec.p, lights.random(rec.p), r.time());
+        let light_pdf = lights.pdf_value(to_light.origin(), to_light.direction());
+        if light_pdf <= 0.0 {
+            return Color::init_zero();
+        }
+
+        let wi = to_light.direction().unit_vector();
+        let wo = -r.direction().unit_vector();
+        let f = material.eval(rec, wi, wo);
+        if f.x() <= 0.0 && f.y() <= 0.0 && f.z() <= 0.0 {
+            return Color::init_zero();
+        }
+
+        // Whatever surface the shadow ray hits first is what the point sees; occluders emit
+        // nothing. It passes medium boundaries, dimmed by each medium on the way
+        let mut media = media.to_vec();
+        cross_boundary(&mut media, rec, to_light.direction());
+        let mut tr = Color::new(1.0, 1.0, 1.0);
+        for _ in 0..=MAX_CROSSINGS {
+            let mut light_rec = HitRecord::new();
+            if !world.hit(&to_light, Interval::new(0.001, f64::INFINITY), &mut light_rec) {
+                return Color::init_zero();
+            }
+            if let Some(behind) = surface_behind(world, &to_light, &light_rec) {
+                light_rec = behind;
+            }
+            let surface = light_rec.material.unwrap();
+            let emitted = surface.emitted(light_rec.u, light_rec.v, &light_rec.p);
+            if !surface.is_invisible() && emitted.x() <= 0.0 && emitted.y() <= 0.0 && emitted.z() <= 0.0 {
+                return Color::init_zero();
+            }
 
-        let mut color_from_emission = material.emitted(rec.u, rec.v, &rec.p);
+            if let Some((medium, frame)) = segment_medium(&media, &light_rec) {
+                tr = tr * medium.transmittance(&frame.to_object(&to_light), light_rec.t);
+                if tr.x() <= 0.0 && tr.y() <= 0.0 && tr.z() <= 0.0 {
+                    return Color::init_zero();
+                }
+            }
+
+            if surface.is_invisible() {
+                cross_boundary(&mut media, &light_rec, to_light.direction());
+                to_light = to_light.transformed(light_rec.p, to_light.direction());
+                continue;
+            }
+
+            let weight = power_heuristic(light_pdf, material.pdf(rec, wi, wo));
+            return f * emitted * tr * (weight / light_pdf);
+        }
 
-        if !material.scatter(r, &rec, &mut attentuation, &mut scattered) {
-            return color_from_emission;
+        Color::init_zero()
+    }
+
+    // Media the camera starts in: follow a ray out of the scene, and every medium it leaves
+    // without having entered it on the way encloses the camera
+    fn starting_media<'a>(&self, world: &'a impl Hittable) -> Vec<PlacedMedium<'a>> {
+        let mut ray = Ray::new(self.center, -self.w);
+        let mut entered: Vec<PlacedMedium> = Vec::new();
+        let mut enclosing = Vec::new();
+
+        for _ in 0..MAX_CROSSINGS {
+            let mut rec = HitRecord::new();
+            if !world.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rec) {
+                break;
+            }
+            if let Some(medium) = placed_medium(&rec) {
+                if rec.front_face {
+                    entered.push(medium);
+                } else if !remove_medium(&mut entered, medium.0) {
+                    enclosing.push(medium);
+                }
+            }
+            ray = ray.transformed(rec.p, ray.direction());
         }
 
-        let color_from_scatter = attentuation * self.ray_color(&scattered, max_depth - 1, world);
-        return color_from_emission + color_from_scatter;
+        // Found innermost first
+        enclosing.reverse();
+        enclosing
     }
 
 }
+
+// Guard against paths (and the camera probe) stuck crossing medium boundaries forever
+const MAX_CROSSINGS: u32 = 256;
+
+// A medium a path is in, with the frame of the object whose boundary it entered through
+type PlacedMedium<'a> = (&'a dyn Medium, Frame);
+
+// The medium bounded by the surface at `rec`, if any
+fn placed_medium<'a>(rec: &HitRecord<'a>) -> Option<PlacedMedium<'a>> {
+    rec.material.and_then(|m| m.medium()).map(|medium| (medium, rec.frame))
+}
+
+// A visible surface coinciding with the medium boundary at `rec`, or just behind it, which the
+// boundary hid
+fn surface_behind<'a>(world: &'a impl Hittable, r: &Ray, rec: &HitRecord) -> Option<HitRecord<'a>> {
+    if !rec.material.unwrap().is_invisible() {
+        return None;
+    }
+    let mut behind = HitRecord::new();
+    let around = Interval::new(rec.t.next_down(), rec.t + 0.001);
+    let hit = world.hit(&r.ignoring_media(), around, &mut behind) && !behind.material.unwrap().is_invisible();
+    hit.then_some(behind)
+}
+
+// Update the media a path is in as it leaves the surface at `rec` in `direction`. Going through a
+// surface that bounds a medium enters the medium from the front and leaves it from the back;
+// bouncing off it changes nothing
+fn cross_boundary<'a>(media: &mut Vec<PlacedMedium<'a>>, rec: &HitRecord<'a>, direction: Vec3) {
+    let Some(medium) = placed_medium(rec) else {
+        return;
+    };
+    // The normal faces where the ray came from
+    if direction.dot(rec.normal) >= 0.0 {
+        return;
+    }
+    if rec.front_face {
+        media.push(medium);
+    } else {
+        remove_medium(media, medium.0);
+    }
+}
+
+// The medium a ray travels through up to the surface at `rec` (which may be a miss): the
+// innermost one the path is in, unless the ray is leaving a medium it never knowingly entered.
+// Then the entry was too close behind the last vertex to be hit, and it has been in there all
+// along
+fn segment_medium<'a>(media: &[PlacedMedium<'a>], rec: &HitRecord<'a>) -> Option<PlacedMedium<'a>> {
+    match placed_medium(rec) {
+        Some(medium) if !rec.front_face && !media.iter().any(|&(m, _)| std::ptr::addr_eq(m, medium.0)) => Some(medium),
+        _ => media.last().copied(),
+    }
+}
+
+// Drop the innermost entry of `medium`; media needn't nest, so it may not be the last one
+fn remove_medium(media: &mut Vec<PlacedMedium>, medium: &dyn Medium) -> bool {
+    match media.iter().rposition(|&(m, _)| std::ptr::addr_eq(m, medium)) {
+        Some(i) => {
+            media.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+// Power heuristic (beta = 2) weight for a sample drawn with density pdf_a when pdf_b
+// could also have produced it
+#[inline]
+fn power_heuristic(pdf_a: f64, pdf_b: f64) -> f64 {
+    let a2 = pdf_a * pdf_a;
+    let b2 = pdf_b * pdf_b;
+    if a2 + b2 == 0.0 { 0.0 } else { a2 / (a2 + b2) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Dielectric, DiffuseLight, Lambertian, ScatterRecord};
+    use crate::quad::Quad;
+    use crate::ray::point_to_ray;
+    use crate::sphere::Sphere;
+    use std::sync::Arc;
+
+    // Lambertian wall that also glows
+    struct GlowingWall {
+        diffuse: Lambertian,
+        light: DiffuseLight,
+    }
+
+    impl Material for GlowingWall {
+        fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+            self.diffuse.sample(r_in, rec)
+        }
+
+        fn eval(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> Color {
+            self.diffuse.eval(rec, wi, wo)
+        }
+
+        fn pdf(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> f64 {
+            self.diffuse.pdf(rec, wi, wo)
+        }
+
+        fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+            self.light.emitted(u, v, p)
+        }
+    }
+
+    // Furnace test: inside a closed sphere whose walls emit `le` and reflect `albedo`, the n-th
+    // ray along a path adds albedo^(n-1) * le, so a path of at most `depth` rays sees
+    // le * (1 - albedo^depth) / (1 - albedo). Russian roulette must not change that, capped or not
+    fn furnace(depth: u32, diffuse_depth: u32) -> (f64, f64) {
+        let (albedo, le) = (0.8, 1.0);
+        let wall = GlowingWall {
+            diffuse: Lambertian::new(Color::new(albedo, albedo, albedo)),
+            light: DiffuseLight::new(Color::new(le, le, le)),
+        };
+        let mut world = HittableList::new();
+        world.add(Box::new(Sphere::new(point_to_ray(Point3::init_zero()), 1.0, Arc::new(wall))));
+
+        let mut camera = Camera::new();
+        camera.aspect_ratio = 1.0;
+        camera.image_width = 8;
+        camera.samples_per_pixel = 400;
+        camera.max_depth = depth;
+        camera.max_diffuse_depth = diffuse_depth;
+        camera.look_from = Point3::new(0.0, 0.0, 0.5);
+        camera.look_at = Point3::init_zero();
+
+        let mean = mean_radiance(&camera.render(&world));
+        let rays = depth.min(diffuse_depth + 1);
+        let expected = le * (1.0 - albedo.powi(rays as i32)) / (1.0 - albedo);
+        (mean, expected)
+    }
+
+    #[test]
+    fn furnace_converges_to_analytic_radiance() {
+        // Uncapped in practice, and capped below and above where roulette starts
+        for (depth, diffuse_depth) in [(1000, 1000), (2, 50), (5, 50), (50, 6)] {
+            let (mean, expected) = furnace(depth, diffuse_depth);
+            assert!(
+                (mean - expected).abs() < 0.05 * expected,
+                "furnace radiance {} != {} at depth {}, diffuse depth {}", mean, expected, depth, diffuse_depth,
+            );
+        }
+    }
+
+    fn mean_radiance(image: &FrameBuffer) -> f64 {
+        let pixels = image.pixels();
+        pixels.iter().map(|c| c.x() + c.y() + c.z()).sum::<f64>() / (3 * pixels.len()) as f64
+    }
+
+    // Sampling the light directly changes the noise, not the answer
+    #[test]
+    fn light_sampling_agrees_with_brute_force() {
+        let grey = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let lamp = Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
+        let light = || Quad::new(Point3::new(-0.5, 1.5, -0.5), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), lamp.clone());
+
+        let mut world = HittableList::new();
+        world.add(Box::new(Quad::new(Point3::new(-3.0, 0.0, -3.0), Vec3::new(6.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 6.0), grey.clone())));
+        world.add(Box::new(Sphere::new(point_to_ray(Point3::new(0.4, 0.4, 0.2)), 0.4, grey)));
+        world.add(Box::new(light()));
+        let mut lights = HittableList::new();
+        lights.add(Box::new(light()));
+
+        let mut camera = Camera::new();
+        camera.aspect_ratio = 1.0;
+        camera.image_width = 16;
+        camera.samples_per_pixel = 400;
+        camera.background_color = Color::init_zero();
+        camera.look_from = Point3::new(0.0, 3.0, 3.0);
+        camera.look_at = Point3::init_zero();
+        camera.vfov = 50.0;
+
+        let with_nee = mean_radiance(&camera.render_with_lights(&world, &lights));
+        let without = mean_radiance(&camera.render(&world));
+        assert!(with_nee > 0.0);
+        assert!((with_nee - without).abs() < 0.03 * with_nee, "with light sampling {} vs without {}", with_nee, without);
+    }
+
+    // Tinted glass dims what is inside it by the distance travelled through the glass alone
+    #[test]
+    fn tinted_glass_dims_objects_inside() {
+        let sigma = Color::new(0.2, 1.0, 3.0);
+        let mut world = HittableList::new();
+        // An index of 1 doesn't reflect head-on, so the light is only seen through 0.5 of glass
+        let glass = Dielectric::new(1.0).with_absorption(sigma);
+        world.add(Box::new(Sphere::new(point_to_ray(Point3::init_zero()), 1.0, Arc::new(glass))));
+        world.add(Box::new(Sphere::new(point_to_ray(Point3::init_zero()), 0.5, Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0))))));
+
+        let mut camera = Camera::new();
+        camera.aspect_ratio = 1.0;
+        camera.image_width = 1;
+        camera.samples_per_pixel = 4;
+        camera.vfov = 0.01;
+        camera.background_color = Color::init_zero();
+        camera.look_from = Point3::new(0.0, 0.0, 5.0);
+        camera.look_at = Point3::init_zero();
+
+        let seen = camera.render(&world).get(0, 0);
+        for c in 0..3 {
+            let expected = (-sigma[c] * 0.5).exp();
+            assert!((seen[c] - expected).abs() < 1e-3, "channel {}: {} != {}", c, seen[c], expected);
+        }
+    }
+}
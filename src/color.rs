@@ -1,6 +1,6 @@
 use crate::vec3::Color;
 use crate::interval::Interval;
-use std::io::Write;
+use std::io::{self, Write};
 
 impl Color{
 
@@ -12,23 +12,24 @@ impl Color{
         return 0.0;
     }
 
-    pub fn write_color<W: Write>(out: &mut W, pixel_color: Color) {
-        let r = pixel_color.x();
-        let g = pixel_color.y();
-        let b = pixel_color.z();
-
-        // Apply gamma correction with gamma=2.0
-        let r = Color::linear_to_gamma(r);
-        let g = Color::linear_to_gamma(g);
-        let b = Color::linear_to_gamma(b);
+    // Gamma correct (gamma=2.0) and quantize a linear color to 8 bits per channel
+    pub fn to_rgb8(pixel_color: Color) -> [u8; 3] {
+        let r = Color::linear_to_gamma(pixel_color.x());
+        let g = Color::linear_to_gamma(pixel_color.y());
+        let b = Color::linear_to_gamma(pixel_color.z());
 
         // Translate thh [0,1] range to [0,255]
         let intensity:Interval = Interval::new(0.000, 0.999);
-        let ir = (256.0 * intensity.clamp(r)) as i32;
-        let ig = (256.0 * intensity.clamp(g)) as i32;
-        let ib = (256.0 * intensity.clamp(b)) as i32;
+        let ir = (256.0 * intensity.clamp(r)) as u8;
+        let ig = (256.0 * intensity.clamp(g)) as u8;
+        let ib = (256.0 * intensity.clamp(b)) as u8;
+        [ir, ig, ib]
+    }
+
+    pub fn write_color<W: Write>(out: &mut W, pixel_color: Color) -> io::Result<()> {
+        let [ir, ig, ib] = Color::to_rgb8(pixel_color);
         // buffered write
-        writeln!(out, "{ir} {ig} {ib}").unwrap();
+        writeln!(out, "{ir} {ig} {ib}")
     }
 
 
@@ -1,78 +1,56 @@
 // src/constant_medium.rs
+// A medium filling a closed boundary. Hits on the boundary come back with an invisible
+// MediumInterface as their material, so the camera steps into (or out of) the medium and goes on;
+// the boundary's own material is never used.
 use std::sync::Arc;
 use crate::hittable::{Hittable, HitRecord};
-use crate::material::Material;
+use crate::material::{Material, HenyeyGreenstein};
+use crate::medium::{Medium, MediumInterface, HomogeneousMedium};
 use crate::ray::Ray;
 use crate::interval::Interval;
 use crate::aabb::AABB;
-use crate::utils::prelude::random_f64;
-use crate::vec3::{Vec3, Color, Point3};
-use crate::material::Isotropic;
-use std::f64::{INFINITY, NEG_INFINITY};
+use crate::vec3::Color;
 
 pub struct constant_medium {
     pub boundary: Arc<dyn Hittable>,
-    neg_inv_density: f64,
-    phase_function: Arc<dyn Material>,
+    interface: MediumInterface,
 }
 
 impl constant_medium {
     pub fn new(boundary: Arc<dyn Hittable>, density: f64, phase_function: Arc<dyn Material>) -> Self {
-        Self { boundary, neg_inv_density: -1.0 / density, phase_function }
+        Self::from_medium(boundary, Arc::new(HomogeneousMedium::new(density, phase_function)))
     }
 
     pub fn from_color(boundary: Arc<dyn Hittable>, density: f64, albedo: &Color) -> Self {
-        Self { boundary, neg_inv_density: -1.0 / density, phase_function: Arc::new(Isotropic::new(*albedo)) }
+        Self::from_medium(boundary, Arc::new(HomogeneousMedium::from_color(density, albedo)))
     }
 
-    //pub fn bounding_box(&self) -> AABB {
-    //    self.boundary.bounding_box()
-    //}
+    // Medium scattering with a Henyey-Greenstein phase function; g > 0 scatters forward
+    pub fn from_color_anisotropic(boundary: Arc<dyn Hittable>, density: f64, albedo: &Color, g: f64) -> Self {
+        Self::new(boundary, density, Arc::new(HenyeyGreenstein::new(*albedo, g)))
+    }
+
+    // Any other medium (a ChromaticMedium or HeterogeneousMedium) inside the boundary
+    pub fn from_medium(boundary: Arc<dyn Hittable>, medium: Arc<dyn Medium>) -> Self {
+        Self { boundary, interface: MediumInterface::invisible(medium) }
+    }
 }
 
 impl Hittable for constant_medium {
     fn hit<'a>(&'a self, r: &Ray, interval: Interval, rec: &mut HitRecord<'a>) -> bool {
-        let mut rec1 = HitRecord::new();
-        let mut rec2 = HitRecord::new();
-
-        // Note: Interval::UNIVERSE or new(-inf, inf) depending on your utils
-        if !self.boundary.hit(r, Interval::new(NEG_INFINITY, INFINITY), &mut rec1){
-            return false;
-        }
-
-        if !self.boundary.hit(r, Interval::new(rec1.t + 0.0001, INFINITY), &mut rec2) {
-            return false;
-        }
-
-        if rec1.t < interval.min{ rec1.t = interval.min;}
-        if rec2.t > interval.max{ rec2.t = interval.max;}
-
-        if rec1.t >= rec2.t {
+        if r.ignores_media() {
             return false;
         }
-
-        if rec1.t < 0.0 {
-            rec1.t = 0.0;
-        }
-
-        let ray_length = r.direction().length();
-        let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
-        let hit_distance = self.neg_inv_density * random_f64().ln();
-
-        if hit_distance > distance_inside_boundary {
+        // Win ties with a surface at exactly the same distance (the glass sphere around smoke
+        // filling it), so the camera always sees the boundary and then looks for the surface
+        let interval = Interval::new(interval.min, interval.max.next_up());
+        if !self.boundary.hit(r, interval, rec) {
             return false;
         }
-
-        rec.t = rec1.t + hit_distance / ray_length;
-        rec.p = r.at(rec.t);
-
-        rec.normal = Vec3::new(1.0, 0.0, 0.0);
-        rec.front_face = true;
-        
-        // This assignment works now because 'a self ensures phase_function lives long enough
-        rec.material = Some(self.phase_function.as_ref());
-
-        return true;
+        // The medium lives in this object's frame, not the boundary's: a density grid is
+        // stretched over the boundary as placed
+        rec.set_material(&self.interface);
+        true
     }
 
     fn bounding_box(&self) -> AABB {
@@ -0,0 +1,313 @@
+// density.rs
+// Spatially varying density for heterogeneous media: a voxel grid loaded from disk, Perlin
+// turbulence, or any texture read as a scalar.
+//
+// Grid files are either Mitsuba-style `.vol` (magic "VOL", version 3, float32 encoding, with the
+// resolution and channel count in the header; several channels are averaged) or headerless
+// little-endian float32 with the resolution given separately. Either way x varies fastest, and
+// the grid is stretched over the bounds it is given, usually the medium's boundary box.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::perlin::Perlin;
+use crate::texture::Texture;
+use crate::vec3::Point3;
+
+pub trait DensityField: Send + Sync {
+    fn density(&self, p: &Point3) -> f64;
+
+    // An upper bound on density() everywhere, the majorant for delta and ratio tracking
+    fn max_density(&self) -> f64;
+}
+
+#[derive(Debug)]
+pub enum GridError {
+    Io { path: PathBuf, source: io::Error },
+    Format { path: PathBuf, message: String },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::Io { path, source } => write!(f, "cannot read {}: {}", path.display(), source),
+            GridError::Format { path, message } => write!(f, "{}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for GridError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GridError::Io { source, .. } => Some(source),
+            GridError::Format { .. } => None,
+        }
+    }
+}
+
+// Dense voxel grid, trilinearly interpolated between voxel centers and zero outside its bounds
+
+pub struct DensityGrid {
+    resolution: [usize; 3],
+    values: Vec<f32>,
+    bounds: AABB,
+    max: f64,
+}
+
+impl DensityGrid {
+    pub fn new(resolution: [usize; 3], values: Vec<f32>, bounds: AABB) -> Result<Self, String> {
+        let count = voxel_count(resolution)?;
+        if values.len() != count {
+            return Err(format!("{} grid values do not match resolution {:?}", values.len(), resolution));
+        }
+        let max = values.iter().fold(0.0f32, |m, &v| m.max(v)) as f64;
+        Ok(Self { resolution, values, bounds, max })
+    }
+
+    // `resolution` is needed for raw files and ignored for .vol files, which carry their own
+    pub fn load<P: AsRef<Path>>(path: P, resolution: Option<[usize; 3]>, bounds: AABB) -> Result<Self, GridError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|source| GridError::Io { path: path.to_path_buf(), source })?;
+        let error = |message: String| GridError::Format { path: path.to_path_buf(), message };
+
+        let (resolution, values) = if bytes.starts_with(b"VOL") {
+            parse_vol(&bytes).map_err(error)?
+        } else {
+            let resolution = resolution.ok_or_else(|| error("raw grid needs a resolution".to_string()))?;
+            let count = voxel_count(resolution).map_err(error)?;
+            if count.checked_mul(4) != Some(bytes.len()) {
+                return Err(error(format!("expected {} float32 values for {:?}, found {} bytes", count, resolution, bytes.len())));
+            }
+            (resolution, read_f32s(&bytes))
+        };
+
+        Self::new(resolution, values, bounds).map_err(error)
+    }
+
+    #[inline]
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        self.values[(z * self.resolution[1] + y) * self.resolution[0] + x] as f64
+    }
+}
+
+// Mitsuba grid volume: "VOL", version byte, then little-endian i32 encoding (1 = float32),
+// resolution x/y/z and channel count, a float32 bounding box and the data
+fn parse_vol(bytes: &[u8]) -> Result<([usize; 3], Vec<f32>), String> {
+    const HEADER: usize = 48;
+    if bytes.len() < HEADER {
+        return Err("truncated .vol header".to_string());
+    }
+    if bytes[3] != 3 {
+        return Err(format!(".vol version {} is not supported", bytes[3]));
+    }
+    let int = |at: usize| i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    if int(4) != 1 {
+        return Err(format!(".vol encoding {} is not supported, only float32 (1)", int(4)));
+    }
+    let (nx, ny, nz, channels) = (int(8), int(12), int(16), int(20));
+    if nx <= 0 || ny <= 0 || nz <= 0 || channels <= 0 {
+        return Err(format!("bad .vol resolution {}x{}x{} with {} channels", nx, ny, nz, channels));
+    }
+
+    let resolution = [nx as usize, ny as usize, nz as usize];
+    let channels = channels as usize;
+    let too_large = || format!("{}x{}x{} grid with {} channels is too large", nx, ny, nz, channels);
+    let floats = voxel_count(resolution)?.checked_mul(channels).ok_or_else(too_large)?;
+    if floats.checked_mul(4).ok_or_else(too_large)? != bytes.len() - HEADER {
+        return Err(format!("expected {} float32 values after the .vol header, found {} bytes", floats, bytes.len() - HEADER));
+    }
+
+    let data = read_f32s(&bytes[HEADER..]);
+    let values = data.chunks_exact(channels).map(|c| c.iter().sum::<f32>() / channels as f32).collect();
+    Ok((resolution, values))
+}
+
+// Number of voxels in a grid, or an error for empty or overflowing resolutions
+fn voxel_count(resolution: [usize; 3]) -> Result<usize, String> {
+    if resolution.contains(&0) {
+        return Err(format!("grid resolution {:?} has an empty axis", resolution));
+    }
+    resolution[0]
+        .checked_mul(resolution[1])
+        .and_then(|n| n.checked_mul(resolution[2]))
+        .ok_or_else(|| format!("grid resolution {:?} is too large", resolution))
+}
+
+fn read_f32s(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect()
+}
+
+impl DensityField for DensityGrid {
+    fn density(&self, p: &Point3) -> f64 {
+        let axes = [self.bounds.x, self.bounds.y, self.bounds.z];
+        let mut base = [0usize; 3];
+        let mut frac = [0.0; 3];
+        for a in 0..3 {
+            let local = (p[a] - axes[a].min) / axes[a].size();
+            if !(0.0..=1.0).contains(&local) {
+                return 0.0;
+            }
+            // Voxel centers sit at (i + 0.5) / n; clamp so the border voxels extend to the edge
+            let n = self.resolution[a];
+            let x = (local * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            base[a] = (x as usize).min(n.saturating_sub(2));
+            frac[a] = x - base[a] as f64;
+        }
+
+        let next = |a: usize| if self.resolution[a] > 1 { base[a] + 1 } else { base[a] };
+        let mut accum = 0.0;
+        for (i, x) in [base[0], next(0)].into_iter().enumerate() {
+            for (j, y) in [base[1], next(1)].into_iter().enumerate() {
+                for (k, z) in [base[2], next(2)].into_iter().enumerate() {
+                    let w = (if i == 1 { frac[0] } else { 1.0 - frac[0] })
+                        * (if j == 1 { frac[1] } else { 1.0 - frac[1] })
+                        * (if k == 1 { frac[2] } else { 1.0 - frac[2] });
+                    accum += w * self.voxel(x, y, z);
+                }
+            }
+        }
+        accum.max(0.0)
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max
+    }
+}
+
+// Procedural cloud: Perlin turbulence, with everything below `cutoff` left empty and the rest
+// rescaled to 0..1
+
+pub struct NoiseDensity {
+    noise: Perlin,
+    frequency: f64,
+    cutoff: f64,
+}
+
+impl NoiseDensity {
+    pub fn new(frequency: f64, cutoff: f64) -> Self {
+        Self { noise: Perlin::new(), frequency, cutoff: cutoff.clamp(0.0, 0.99) }
+    }
+}
+
+impl DensityField for NoiseDensity {
+    fn density(&self, p: &Point3) -> f64 {
+        let turb = self.noise.turb(&(*p * self.frequency), 7);
+        ((turb - self.cutoff) / (1.0 - self.cutoff)).clamp(0.0, 1.0)
+    }
+
+    fn max_density(&self) -> f64 {
+        1.0
+    }
+}
+
+// Any texture (a NoiseTexture, say) sampled at the point, clamped to 0..1
+
+pub struct TextureDensity {
+    texture: Arc<dyn Texture>,
+}
+
+impl TextureDensity {
+    pub fn new(texture: Arc<dyn Texture>) -> Self {
+        Self { texture }
+    }
+}
+
+impl DensityField for TextureDensity {
+    fn density(&self, p: &Point3) -> f64 {
+        self.texture.scalar(0.0, 0.0, p).clamp(0.0, 1.0)
+    }
+
+    fn max_density(&self) -> f64 {
+        1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interval::Interval;
+
+    // A float32 .vol file: header, a unit bounding box and `values`
+    fn vol(version: u8, resolution: [i32; 3], channels: i32, values: &[f32]) -> Vec<u8> {
+        let mut bytes = b"VOL".to_vec();
+        bytes.push(version);
+        for n in [1, resolution[0], resolution[1], resolution[2], channels] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        for b in [0.0f32, 0.0, 0.0, 1.0, 1.0, 1.0] {
+            bytes.extend_from_slice(&b.to_le_bytes());
+        }
+        for v in values {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn vol_round_trip() {
+        let values: Vec<f32> = (0..8).map(|i| i as f32).collect();
+        let (resolution, parsed) = parse_vol(&vol(3, [2, 2, 2], 1, &values)).unwrap();
+        assert_eq!(resolution, [2, 2, 2]);
+        assert_eq!(parsed, values);
+    }
+
+    #[test]
+    fn vol_channels_are_averaged() {
+        let (_, parsed) = parse_vol(&vol(3, [2, 1, 1], 2, &[1.0, 3.0, 0.0, 4.0])).unwrap();
+        assert_eq!(parsed, vec![2.0, 2.0]);
+    }
+
+    #[test]
+    fn vol_header_mismatch() {
+        // One value short of what the header promises
+        let error = parse_vol(&vol(3, [2, 2, 2], 1, &[0.0; 7])).unwrap_err();
+        assert_eq!(error, "expected 8 float32 values after the .vol header, found 28 bytes");
+
+        let error = parse_vol(&vol(4, [2, 2, 2], 1, &[0.0; 8])).unwrap_err();
+        assert_eq!(error, ".vol version 4 is not supported");
+
+        let error = parse_vol(&vol(3, [2, 0, 2], 1, &[])).unwrap_err();
+        assert!(error.starts_with("bad .vol resolution"), "{}", error);
+
+        assert_eq!(parse_vol(b"VOL\x03").unwrap_err(), "truncated .vol header");
+
+        // A hostile header whose size overflows must not wrap around to match the data
+        let error = parse_vol(&vol(3, [i32::MAX; 3], 1, &[])).unwrap_err();
+        assert!(error.ends_with("is too large"), "{}", error);
+    }
+
+    #[test]
+    fn empty_resolution_is_rejected() {
+        let bounds = AABB::new(Interval::new(0.0, 1.0), Interval::new(0.0, 1.0), Interval::new(0.0, 1.0));
+        assert!(DensityGrid::new([0, 1, 1], Vec::new(), bounds).is_err());
+        assert!(DensityGrid::new([2, 1, 1], vec![1.0], bounds).is_err());
+
+        let path = std::env::temp_dir().join(format!("rusty_raytracing_empty_grid_{}.raw", std::process::id()));
+        fs::write(&path, []).unwrap();
+        let result = DensityGrid::load(&path, Some([0, 1, 1]), bounds);
+        fs::remove_file(&path).ok();
+        match result {
+            Err(GridError::Format { message, .. }) => assert_eq!(message, "grid resolution [0, 1, 1] has an empty axis"),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("a grid with an empty axis should not load"),
+        }
+    }
+
+    #[test]
+    fn raw_grid_size_mismatch() {
+        let path = std::env::temp_dir().join(format!("rusty_raytracing_grid_{}.raw", std::process::id()));
+        fs::write(&path, [0u8; 12]).unwrap();
+        let bounds = AABB::new(Interval::new(0.0, 1.0), Interval::new(0.0, 1.0), Interval::new(0.0, 1.0));
+        let result = DensityGrid::load(&path, Some([2, 1, 1]), bounds);
+        fs::remove_file(&path).ok();
+        match result {
+            Err(GridError::Format { message, .. }) => assert_eq!(message, "expected 2 float32 values for [2, 1, 1], found 12 bytes"),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("a 12-byte grid should not load as 2 values"),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+// framebuffer.rs
+
+use std::io::{self, Write};
+use crate::vec3::Color;
+
+// Owned image buffer produced by Camera::render.
+// Pixels are stored row-major, top row first, as linear (un-gamma'd, unclamped) HDR colors.
+#[derive(Clone, Debug)]
+pub struct FrameBuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::init_zero(); (width as usize) * (height as usize)],
+        }
+    }
+
+    // Build a buffer from rows that are already in top-to-bottom order
+    pub fn from_rows(width: u32, height: u32, rows: Vec<Vec<Color>>) -> Self {
+        debug_assert_eq!(rows.len(), height as usize);
+        let pixels: Vec<Color> = rows.into_iter().flatten().collect();
+        debug_assert_eq!(pixels.len(), (width as usize) * (height as usize));
+        Self { width, height, pixels }
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    #[inline]
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    #[inline]
+    pub fn get(&self, x: u32, y: u32) -> Color {
+        self.pixels[self.index(x, y)]
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: u32, y: u32, color: Color) {
+        let idx = self.index(x, y);
+        self.pixels[idx] = color;
+    }
+
+    // Iterate over rows, top row first
+    pub fn rows(&self) -> std::slice::Chunks<'_, Color> {
+        self.pixels.chunks(self.width.max(1) as usize)
+    }
+
+    // Write the buffer as ASCII P3 PPM using Color::write_color (gamma 2, 8 bits per channel)
+    pub fn write_ppm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "P3\n{} {}\n255", self.width, self.height)?;
+        for c in &self.pixels {
+            Color::write_color(out, *c)?;
+        }
+        out.flush()
+    }
+
+    #[inline]
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "FrameBuffer index ({}, {}) out of bounds", x, y);
+        (y as usize) * (self.width as usize) + (x as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Accepts `room` bytes, then fails like a closed pipe
+    struct ClosedPipe {
+        room: usize,
+    }
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.room == 0 {
+                return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+            }
+            let n = buf.len().min(self.room);
+            self.room -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_ppm_reports_write_errors() {
+        let buffer = FrameBuffer::new(4, 4);
+        let error = buffer.write_ppm(&mut ClosedPipe { room: 20 }).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+
+        let mut bytes = Vec::new();
+        buffer.write_ppm(&mut bytes).unwrap();
+        assert!(bytes.starts_with(b"P3\n4 4\n255\n0 0 0\n"));
+    }
+}
@@ -0,0 +1,101 @@
+// heterogeneous_medium.rs
+// Participating medium whose density varies from point to point, for clouds, smoke and
+// explosions; put it in a boundary with constant_medium::from_medium. Free flights are sampled by
+// delta tracking against the field's maximum density, and shadow rays get their transmittance by
+// ratio tracking. An emitting medium (fire, glowing gas) adds its light at every collision, as
+// emission / extinction.
+
+use std::sync::Arc;
+use crate::density::DensityField;
+use crate::hittable::HitRecord;
+use crate::material::{Material, Isotropic};
+use crate::medium::{Medium, MediumSample, EmissivePhase, scatter_at};
+use crate::ray::Ray;
+use crate::utils::prelude::random_f64;
+use crate::vec3::Color;
+
+pub struct HeterogeneousMedium {
+    density: Arc<dyn DensityField>,
+    density_scale: f64, // extinction per unit length where the field is 1
+    phase_function: Arc<dyn Material>,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(density: Arc<dyn DensityField>, density_scale: f64, phase_function: Arc<dyn Material>) -> Self {
+        Self { density, density_scale, phase_function }
+    }
+
+    pub fn from_color(density: Arc<dyn DensityField>, density_scale: f64, albedo: &Color) -> Self {
+        Self::new(density, density_scale, Arc::new(Isotropic::new(*albedo)))
+    }
+
+    // Radiance emitted per unit length where the field is 1, so it follows the density
+    pub fn with_emission(mut self, emission: Color) -> Self {
+        let emission = emission / self.density_scale;
+        self.phase_function = Arc::new(EmissivePhase::new(self.phase_function, emission));
+        self
+    }
+
+    #[inline]
+    fn majorant(&self) -> f64 {
+        self.density_scale * self.density.max_density()
+    }
+
+    // Distance in ray parameter to the next tentative collision with the majorant medium
+    #[inline]
+    fn step(&self, r: &Ray) -> f64 {
+        -(1.0 - random_f64()).ln() / (self.majorant() * r.direction().length())
+    }
+}
+
+impl Medium for HeterogeneousMedium {
+    fn sample<'a>(&'a self, r: &Ray, t_max: f64, rec: &mut HitRecord<'a>) -> MediumSample {
+        let through = MediumSample { scattered: false, weight: Color::new(1.0, 1.0, 1.0) };
+        // A ray leaving through a gap in the boundary never ends; let it go rather than march
+        // through the field forever
+        let majorant = self.majorant();
+        if majorant <= 0.0 || !t_max.is_finite() {
+            return through;
+        }
+
+        // Delta tracking: collide with the majorant, keep the collision with probability
+        // density / majorant, otherwise it was a null collision and the flight goes on
+        let mut t = 0.0;
+        loop {
+            t += self.step(r);
+            if t >= t_max {
+                return through;
+            }
+            if random_f64() * majorant < self.density_scale * self.density.density(&r.at(t)) {
+                scatter_at(r, t, self.phase_function.as_ref(), rec);
+                return MediumSample { scattered: true, weight: Color::new(1.0, 1.0, 1.0) };
+            }
+        }
+    }
+
+    // Ratio tracking: the same tentative collisions, each scaling the estimate by the chance it
+    // was a null one. Roulette ends flights once little is left
+    fn transmittance(&self, r: &Ray, t_max: f64) -> Color {
+        let majorant = self.majorant();
+        if majorant <= 0.0 || !t_max.is_finite() {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+
+        let mut tr = 1.0;
+        let mut t = 0.0;
+        loop {
+            t += self.step(r);
+            if t >= t_max {
+                break;
+            }
+            tr *= 1.0 - (self.density_scale * self.density.density(&r.at(t)) / majorant).min(1.0);
+            if tr < 0.1 {
+                if random_f64() >= 0.5 {
+                    return Color::init_zero();
+                }
+                tr *= 2.0;
+            }
+        }
+        Color::new(tr, tr, tr)
+    }
+}
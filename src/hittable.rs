@@ -3,12 +3,47 @@
 use std::sync::{Arc, OnceLock};
 
 use crate::ray::Ray;
-use crate::vec3::{Point3, Vec3, Color};
+use crate::vec3::{Point3, Vec3};
 use crate::interval::Interval;
 use crate::material::{Material, Lambertian};
 use crate::aabb::AABB;
 
 
+// How the object that was hit is placed in the world by the Translate and RotateY around it: a
+// rotation about y, then an offset. Media evaluate their density in the object's own frame,
+// so a moved cloud takes its density field along
+#[derive(Copy, Clone)]
+pub struct Frame {
+    sin_theta: f64,
+    cos_theta: f64,
+    offset: Vec3,
+}
+
+impl Frame {
+    pub fn identity() -> Self {
+        Self { sin_theta: 0.0, cos_theta: 1.0, offset: Vec3::init_zero() }
+    }
+
+    // The same ray in the object's frame; distances along it are unchanged
+    pub fn to_object(&self, r: &Ray) -> Ray {
+        let rotate = |v: Vec3| Vec3::new(self.cos_theta * v.x() - self.sin_theta * v.z(), v.y(), self.sin_theta * v.x() + self.cos_theta * v.z());
+        r.transformed(rotate(r.origin() - self.offset), rotate(r.direction()))
+    }
+
+    fn translated(self, offset: Vec3) -> Self {
+        Self { offset: self.offset + offset, ..self }
+    }
+
+    fn rotated_y(self, sin_theta: f64, cos_theta: f64) -> Self {
+        let o = self.offset;
+        Self {
+            sin_theta: self.sin_theta * cos_theta + self.cos_theta * sin_theta,
+            cos_theta: self.cos_theta * cos_theta - self.sin_theta * sin_theta,
+            offset: Vec3::new(cos_theta * o.x() + sin_theta * o.z(), o.y(), -sin_theta * o.x() + cos_theta * o.z()),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct HitRecord<'a> {
     pub p: Point3,
@@ -18,6 +53,8 @@ pub struct HitRecord<'a> {
     pub front_face: bool,
     pub u: f64,
     pub v: f64,
+    pub dpdu: Option<Vec3>, // direction of increasing u on the surface, when the shape has one
+    pub frame: Frame,
 }
 
 impl<'a> HitRecord<'a> {
@@ -30,9 +67,19 @@ impl<'a> HitRecord<'a> {
             front_face: true,
             u: 0.0,
             v: 0.0,
+            dpdu: None,
+            frame: Frame::identity(),
         }
     }
 
+    // For primitives: the hit is in their own frame until transforms around them place it.
+    // Clears dpdu, which primitives that have one set afterwards
+    pub fn set_material(&mut self, material: &'a dyn Material) {
+        self.material = Some(material);
+        self.frame = Frame::identity();
+        self.dpdu = None;
+    }
+
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
         self.front_face = r.direction().dot(outward_normal) < 0.0;
         self.normal = if self.front_face { outward_normal } else { -outward_normal };
@@ -46,6 +93,16 @@ pub trait Hittable: Send + Sync {
     fn bounding_box(&self) -> AABB {
         AABB::empty()
     }
+
+    // Light sampling, for shapes registered as lights: the solid-angle density of random()
+    // picking `direction` from `origin`, and a random direction from `origin` toward the shape
+    fn pdf_value(&self, _origin: Point3, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    fn random(&self, _origin: Point3) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
 }
 
 
@@ -71,7 +128,7 @@ impl Translate {
 impl Hittable for Translate {
     fn hit<'a>(&'a self, r: &Ray, interval: Interval, rec: &mut HitRecord<'a>) -> bool {
         // Move the ray backwards by the offset
-        let offset_r = Ray::new_time(r.origin() - self.offset, r.direction(), r.time());
+        let offset_r = r.transformed(r.origin() - self.offset, r.direction());
 
         // Determine whether an intersection exists along the offset ray
         if !self.hittable.hit(&offset_r, interval, rec) {
@@ -80,6 +137,7 @@ impl Hittable for Translate {
 
         // Move the intersection point forward by the offset
         rec.p = rec.p + self.offset;
+        rec.frame = rec.frame.translated(self.offset);
 
         return true;
     }
@@ -87,6 +145,14 @@ impl Hittable for Translate {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.hittable.pdf_value(origin - self.offset, direction)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.hittable.random(origin - self.offset)
+    }
 }
 
 // Rotation around Y axis
@@ -131,6 +197,16 @@ impl RotateY {
 
         Self { object, sin_theta, cos_theta, bbox: rotated_bbox }
     }
+
+    #[inline]
+    fn to_object(&self, p: Vec3) -> Vec3 {
+        Vec3::new(self.cos_theta * p.x() - self.sin_theta * p.z(), p.y(), self.sin_theta * p.x() + self.cos_theta * p.z())
+    }
+
+    #[inline]
+    fn to_world(&self, p: Vec3) -> Vec3 {
+        Vec3::new(self.cos_theta * p.x() + self.sin_theta * p.z(), p.y(), -self.sin_theta * p.x() + self.cos_theta * p.z())
+    }
 }
 
 impl Hittable for RotateY {
@@ -150,7 +226,7 @@ impl Hittable for RotateY {
             self.sin_theta * direction.x() + self.cos_theta * direction.z(),
         );
 
-        let rotated_r = Ray::new_time(rotated_origin, rotated_direction, r.time());
+        let rotated_r = r.transformed(rotated_origin, rotated_direction);
 
         if !self.object.hit(&rotated_r, interval, rec) {
             return false;
@@ -171,8 +247,12 @@ impl Hittable for RotateY {
             -self.sin_theta * normal.x() + self.cos_theta * normal.z(),
         );
 
+        // The object already faced the normal against the ray and rotation keeps that, so
+        // front_face stands; recomputing it against the object-space ray would mix frames
         rec.p = rotated_p;
-        rec.set_face_normal(&rotated_r, rotated_normal);
+        rec.normal = rotated_normal;
+        rec.dpdu = rec.dpdu.map(|t| self.to_world(t));
+        rec.frame = rec.frame.rotated_y(self.sin_theta, self.cos_theta);
 
         return true;
     }
@@ -180,4 +260,13 @@ impl Hittable for RotateY {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    // Solid angles don't change under rotation, so only the query has to be rotated
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        self.object.pdf_value(self.to_object(origin), self.to_object(direction))
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        self.to_world(self.object.random(self.to_object(origin)))
+    }
 }
@@ -4,6 +4,8 @@ use crate::hittable::{Hittable, HitRecord};
 use crate::ray::Ray;
 use crate::interval::Interval;
 use crate::aabb::AABB;
+use crate::vec3::{Point3, Vec3};
+use crate::utils::prelude::random_f64;
 use std::sync::Arc;
 use std::mem;
 
@@ -20,8 +22,11 @@ impl HittableList {
     }
 
     pub fn add(&mut self, object: Box<dyn Hittable>) {
-        let obj: HittablePtr = Arc::from(object);
+        self.add_shared(Arc::from(object));
+    }
 
+    // Add an object that is also referenced elsewhere, e.g. a light in both the world and the light list
+    pub fn add_shared(&mut self, obj: HittablePtr) {
         self.bbox = Some(match self.bbox {
             None => obj.bounding_box(),
             Some(b) => AABB::from_two_boxes(b, obj.bounding_box()),
@@ -66,4 +71,21 @@ impl Hittable for HittableList {
         // If empty list, return empty box.
         self.bbox.unwrap_or_else(AABB::empty)
     }
+
+    // Pick one of the objects uniformly, so the density is the average of theirs
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let weight = 1.0 / self.objects.len() as f64;
+        self.objects.iter().map(|o| weight * o.pdf_value(origin, direction)).sum()
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let index = ((random_f64() * self.objects.len() as f64) as usize).min(self.objects.len() - 1);
+        self.objects[index].random(origin)
+    }
 }
@@ -2,6 +2,7 @@
 
 use image::ImageReader as ImageReader;
 use image::GenericImageView;
+use std::sync::OnceLock;
 use crate::Color;
 
 pub struct ImageTextureData {
@@ -38,10 +39,20 @@ impl ImageTextureData{
             return color;
         }
         let idx = ((y * image.width + x) * 3) as usize;
-        let r = image.rgb[idx] as f64 / 255.0;
-        let g = image.rgb[idx + 1] as f64 / 255.0;
-        let b = image.rgb[idx + 2] as f64 / 255.0;
-        Color::new(Self::srgb_to_linear(r), Self::srgb_to_linear(g), Self::srgb_to_linear(b))
+        // Filtered lookups read up to 16 texels, so decode bytes from a table rather than powf
+        static LINEAR: OnceLock<[f64; 256]> = OnceLock::new();
+        let linear = LINEAR.get_or_init(|| std::array::from_fn(|i| Self::srgb_to_linear(i as f64 / 255.0)));
+        Color::new(linear[image.rgb[idx] as usize], linear[image.rgb[idx + 1] as usize], linear[image.rgb[idx + 2] as usize])
+    }
+
+    // The stored values as they are, for data maps (roughness, metalness, masks) that were
+    // never sRGB-encoded
+    pub fn raw_pixel_data(image: &ImageTextureData, x: u32, y: u32) -> Color {
+        if x >= image.width || y >= image.height {
+            return Color::new(1.0, 0.0, 1.0); // magenta for out-of-bounds
+        }
+        let idx = ((y * image.width + x) * 3) as usize;
+        Color::new(image.rgb[idx] as f64 / 255.0, image.rgb[idx + 1] as f64 / 255.0, image.rgb[idx + 2] as f64 / 255.0)
     }
 
     fn float_to_byte(value: f64) -> u8 {
@@ -0,0 +1,265 @@
+// image_writer.rs
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use exr::prelude::f16;
+use image::{ImageError, ImageFormat, ImageResult, Rgb, RgbImage};
+use image::codecs::hdr::HdrEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::pnm::{PnmEncoder, PnmSubtype, SampleEncoding};
+use image::error::{EncodingError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use crate::framebuffer::FrameBuffer;
+use crate::vec3::Color;
+
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+// Output formats. The 8-bit ones go through Color::to_rgb8 (gamma 2, clamped),
+// the float ones store the linear radiance untouched
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutputFormat {
+    Png,
+    Jpeg { quality: u8 }, // 1..=100
+    Ppm, // binary P6
+    Exr { precision: ExrPrecision },
+    Hdr, // Radiance RGBE
+    Pfm, // Portable Float Map
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExrPrecision {
+    Half,  // 16-bit float, half the size, plenty for display
+    Float, // 32-bit float, lossless
+}
+
+impl OutputFormat {
+    // True for the formats that keep values above 1.0
+    pub fn is_hdr(&self) -> bool {
+        matches!(self, OutputFormat::Exr { .. } | OutputFormat::Hdr | OutputFormat::Pfm)
+    }
+
+    // Pick a format from the file extension (case insensitive)
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg { quality: DEFAULT_JPEG_QUALITY }),
+            "ppm" => Some(OutputFormat::Ppm),
+            "exr" => Some(OutputFormat::Exr { precision: ExrPrecision::Float }),
+            "hdr" => Some(OutputFormat::Hdr),
+            "pfm" => Some(OutputFormat::Pfm),
+            _ => None,
+        }
+    }
+}
+
+// Save a rendered buffer, choosing the format from the file extension
+pub fn save<P: AsRef<Path>>(buffer: &FrameBuffer, path: P) -> ImageResult<()> {
+    let path = path.as_ref();
+    match OutputFormat::from_path(path) {
+        Some(format) => save_with_format(buffer, path, format),
+        None => Err(unsupported_extension(path)),
+    }
+}
+
+pub fn save_with_format<P: AsRef<Path>>(buffer: &FrameBuffer, path: P, format: OutputFormat) -> ImageResult<()> {
+    match format {
+        OutputFormat::Png => save_png(buffer, path),
+        OutputFormat::Jpeg { quality } => save_jpeg(buffer, path, quality),
+        OutputFormat::Ppm => save_ppm(buffer, path),
+        OutputFormat::Exr { precision } => save_exr(buffer, path, precision),
+        OutputFormat::Hdr => save_hdr(buffer, path),
+        OutputFormat::Pfm => save_pfm(buffer, path),
+    }
+}
+
+pub fn save_png<P: AsRef<Path>>(buffer: &FrameBuffer, path: P) -> ImageResult<()> {
+    to_rgb8_image(buffer).save_with_format(path, ImageFormat::Png)
+}
+
+pub fn save_jpeg<P: AsRef<Path>>(buffer: &FrameBuffer, path: P, quality: u8) -> ImageResult<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let encoder = JpegEncoder::new_with_quality(&mut out, quality.clamp(1, 100));
+    to_rgb8_image(buffer).write_with_encoder(encoder)
+}
+
+// Binary P6 PPM, a fraction of the size of the ASCII P3 files FrameBuffer::write_ppm produces
+pub fn save_ppm<P: AsRef<Path>>(buffer: &FrameBuffer, path: P) -> ImageResult<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let encoder = PnmEncoder::new(&mut out).with_subtype(PnmSubtype::Pixmap(SampleEncoding::Binary));
+    to_rgb8_image(buffer).write_with_encoder(encoder)
+}
+
+// OpenEXR with linear RGB channels
+pub fn save_exr<P: AsRef<Path>>(buffer: &FrameBuffer, path: P, precision: ExrPrecision) -> ImageResult<()> {
+    let width = buffer.width() as usize;
+    let height = buffer.height() as usize;
+    let result = match precision {
+        ExrPrecision::Half => exr::prelude::write_rgb_file(path, width, height, |x, y| {
+            let c = buffer.get(x as u32, y as u32);
+            (f16::from_f64(c.x()), f16::from_f64(c.y()), f16::from_f64(c.z()))
+        }),
+        ExrPrecision::Float => exr::prelude::write_rgb_file(path, width, height, |x, y| {
+            let c = buffer.get(x as u32, y as u32);
+            (c.x() as f32, c.y() as f32, c.z() as f32)
+        }),
+    };
+    result.map_err(|e| ImageError::Encoding(EncodingError::new(ImageFormat::OpenExr.into(), e)))
+}
+
+// Radiance RGBE (.hdr): shared 8-bit exponent, about 1% precision over a huge range
+pub fn save_hdr<P: AsRef<Path>>(buffer: &FrameBuffer, path: P) -> ImageResult<()> {
+    let out = BufWriter::new(File::create(path)?);
+    let pixels: Vec<Rgb<f32>> = buffer
+        .pixels()
+        .iter()
+        .map(|c| Rgb([c.x().max(0.0) as f32, c.y().max(0.0) as f32, c.z().max(0.0) as f32]))
+        .collect();
+    HdrEncoder::new(out).encode(&pixels, buffer.width() as usize, buffer.height() as usize)
+}
+
+// Portable Float Map: "PF" header, negative scale for little endian, rows stored bottom to top
+pub fn save_pfm<P: AsRef<Path>>(buffer: &FrameBuffer, path: P) -> ImageResult<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "PF\n{} {}\n-1.0\n", buffer.width(), buffer.height())?;
+    for row in buffer.rows().rev() {
+        for c in row {
+            out.write_all(&(c.x() as f32).to_le_bytes())?;
+            out.write_all(&(c.y() as f32).to_le_bytes())?;
+            out.write_all(&(c.z() as f32).to_le_bytes())?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// Convert the linear HDR buffer to a display-ready 8-bit image
+pub fn to_rgb8_image(buffer: &FrameBuffer) -> RgbImage {
+    let mut raw = Vec::with_capacity(buffer.pixels().len() * 3);
+    for c in buffer.pixels() {
+        raw.extend_from_slice(&Color::to_rgb8(*c));
+    }
+    RgbImage::from_raw(buffer.width(), buffer.height(), raw).expect("FrameBuffer size does not match its dimensions")
+}
+
+fn unsupported_extension(path: &Path) -> ImageError {
+    let hint = match path.extension() {
+        Some(ext) => ImageFormatHint::PathExtension(ext.into()),
+        None => ImageFormatHint::Unknown,
+    };
+    ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+        hint.clone(),
+        UnsupportedErrorKind::Format(hint),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Minimal PFM reader: width, height and the pixels top row first
+    fn read_pfm(bytes: &[u8]) -> (u32, u32, Vec<[f32; 3]>) {
+        let mut lines = bytes.splitn(4, |&b| b == b'\n');
+        assert_eq!(lines.next(), Some(&b"PF"[..]));
+        let size = std::str::from_utf8(lines.next().unwrap()).unwrap();
+        let (w, h) = size.split_once(' ').unwrap();
+        let (w, h): (u32, u32) = (w.parse().unwrap(), h.parse().unwrap());
+        let scale: f32 = std::str::from_utf8(lines.next().unwrap()).unwrap().parse().unwrap();
+        assert!(scale < 0.0, "expected little-endian data");
+
+        let data = lines.next().unwrap();
+        assert_eq!(data.len(), (w * h * 12) as usize);
+        let floats: Vec<f32> = data.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+        let rows: Vec<&[f32]> = floats.chunks_exact(w as usize * 3).collect();
+        let pixels = rows.iter().rev().flat_map(|row| row.chunks_exact(3).map(|c| [c[0], c[1], c[2]])).collect();
+        (w, h, pixels)
+    }
+
+    // 3x2 buffer with HDR values above 1 and tiny ones
+    fn test_buffer() -> FrameBuffer {
+        let mut buffer = FrameBuffer::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                buffer.set(x, y, Color::new(x as f64 * 2.5, y as f64 + 0.125, 1e-3 * (x + 3 * y) as f64));
+            }
+        }
+        buffer
+    }
+
+    fn temp_path(ext: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rusty_raytracing_{}.{}", std::process::id(), ext))
+    }
+
+    #[test]
+    fn pfm_round_trip() {
+        let buffer = test_buffer();
+        let path = temp_path("pfm");
+        save_with_format(&buffer, &path, OutputFormat::Pfm).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let (w, h, pixels) = read_pfm(&bytes);
+        assert_eq!((w, h), (3, 2));
+        for (written, read) in buffer.pixels().iter().zip(&pixels) {
+            assert_eq!([written.x() as f32, written.y() as f32, written.z() as f32], *read);
+        }
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let buffer = test_buffer();
+        let path = temp_path("png");
+        save(&buffer, &path).unwrap();
+        let read = image::open(&path).map(|img| img.to_rgb8());
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(read.unwrap(), to_rgb8_image(&buffer));
+    }
+
+    #[test]
+    fn ppm_is_binary_p6() {
+        let buffer = test_buffer();
+        let path = temp_path("ppm");
+        save(&buffer, &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        // Four whitespace separated header fields, then one whitespace byte before the samples
+        let data = to_rgb8_image(&buffer).into_raw();
+        let (header, samples) = bytes.split_at(bytes.len() - data.len());
+        let fields: Vec<&str> = std::str::from_utf8(header).unwrap().split_whitespace().collect();
+        assert_eq!(fields, ["P6", "3", "2", "255"]);
+        assert!(header.last().unwrap().is_ascii_whitespace());
+        assert_eq!(samples, data.as_slice());
+    }
+
+    #[test]
+    fn hdr_round_trip() {
+        let buffer = test_buffer();
+        let path = temp_path("hdr");
+        save(&buffer, &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let read = image::open(&path).map(|img| img.to_rgb32f());
+        std::fs::remove_file(&path).ok();
+
+        assert!(bytes.starts_with(b"#?RADIANCE"));
+        let read = read.unwrap();
+        assert_eq!(read.dimensions(), (3, 2));
+        for (written, read) in buffer.pixels().iter().zip(read.pixels()) {
+            // RGBE shares one exponent, so each channel is exact to about 1% of the largest
+            let tolerance = 0.01 * written.x().max(written.y()).max(written.z()) as f32;
+            for (w, r) in [written.x(), written.y(), written.z()].iter().zip(read.0) {
+                assert!((*w as f32 - r).abs() <= tolerance, "wrote {:?}, read {:?}", written, read);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_extensions_are_rejected() {
+        assert_eq!(OutputFormat::from_path("render.PNG"), Some(OutputFormat::Png));
+        assert_eq!(OutputFormat::from_path("render.bmp"), None);
+        assert_eq!(OutputFormat::from_path("render"), None);
+        assert!(matches!(save(&test_buffer(), temp_path("bmp")), Err(ImageError::Unsupported(_))));
+    }
+}
@@ -12,11 +12,25 @@ pub mod interval;
 pub mod camera;
 pub mod material;
 pub mod aabb;
+pub mod onb;
+pub mod microfacet;
+pub mod spectrum;
 pub mod bvh;
+pub mod linear_bvh;
 pub mod texture;
 pub mod image_loader;
+pub mod image_writer;
 pub mod perlin;
 pub mod quad;
+pub mod triangle;
+pub mod obj_loader;
+pub mod medium;
 pub mod constant_medium;
+pub mod density;
+pub mod heterogeneous_medium;
+pub mod framebuffer;
+pub mod scene;
+pub mod scenes;
+pub mod scene_file;
 
 pub use crate::utils::prelude::*;
@@ -0,0 +1,377 @@
+// linear_bvh.rs
+// BVH flattened into one array of nodes in depth-first order. A node's first child is the next
+// node in the array, so only the second child's index is stored. Leaves hold a range of the
+// reordered primitive list instead of one duplicated object, and traversal is a loop over a
+// small stack that visits the nearer child first.
+//
+// The node array (FlatTree) does not own its primitives: LinearBVH pairs it with a list of
+// hittables, TriangleMesh with its own triangle indices.
+
+use std::ops::Range;
+use std::time::Instant;
+
+use crate::aabb::AABB;
+use crate::bvh::{self, BuildStats, SplitMethod, INTERSECTION_COST, TRAVERSAL_COST};
+use crate::hittable::{Hittable, HitRecord};
+use crate::hittable_list::{HittableList, HittablePtr};
+use crate::interval::Interval;
+use crate::ray::Ray;
+
+// SAH leaves may hold up to this many primitives when that is cheaper than splitting
+const MAX_LEAF_SIZE: usize = 4;
+
+// Below this depth the SAH builder switches to median splits, which keeps the tree (and so the
+// traversal stack) shallow even for badly clustered input
+const MAX_SAH_DEPTH: usize = 48;
+const STACK_SIZE: usize = 128;
+
+#[derive(Copy, Clone, Debug)]
+enum NodeKind {
+    // Children are at index + 1 and `second_child`; `axis` orders the traversal
+    Interior { second_child: u32, axis: u8 },
+    Leaf { first: u32, count: u32 },
+}
+
+#[derive(Copy, Clone, Debug)]
+struct LinearNode {
+    bbox: AABB,
+    kind: NodeKind,
+}
+
+pub(crate) struct FlatTree {
+    nodes: Vec<LinearNode>,
+}
+
+impl FlatTree {
+    // Build over `primitives`, reordering them so that every leaf covers a contiguous range.
+    // An empty slice gives an empty tree that nothing hits.
+    pub(crate) fn build<T: Clone>(
+        primitives: &mut [T],
+        method: SplitMethod,
+        bbox: &impl Fn(&T) -> AABB,
+    ) -> (Self, BuildStats) {
+        let start = Instant::now();
+        let mut builder = Builder {
+            nodes: Vec::with_capacity(2 * primitives.len()),
+            object_box: bbox,
+            method,
+            stats: BuildStats { primitives: primitives.len(), ..BuildStats::default() },
+        };
+
+        if !primitives.is_empty() {
+            let end = primitives.len();
+            builder.build(primitives, 0, end, 1);
+        }
+        let Builder { nodes, mut stats, .. } = builder;
+        stats.build_time = start.elapsed();
+
+        let root_area = nodes.first().map_or(0.0, |n: &LinearNode| n.bbox.surface_area());
+        stats.sah_cost = if root_area > 0.0 { stats.sah_cost / root_area } else { 0.0 };
+
+        (Self { nodes }, stats)
+    }
+
+    pub(crate) fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub(crate) fn bounding_box(&self) -> AABB {
+        self.nodes.first().map_or_else(AABB::empty, |n| n.bbox)
+    }
+
+    // Visit the leaves the ray reaches, nearer children first. `hit_leaf` tests a range of the
+    // primitives against the interval so far and returns the distance of its closest hit.
+    #[inline]
+    pub(crate) fn hit(&self, r: &Ray, ray_t: Interval, mut hit_leaf: impl FnMut(Range<usize>, Interval) -> Option<f64>) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let dir = r.direction();
+        let dir_is_neg = [dir.x() < 0.0, dir.y() < 0.0, dir.z() < 0.0];
+
+        let mut hit_anything = false;
+        let mut closest_so_far = ray_t.max;
+
+        let mut stack = [0u32; STACK_SIZE];
+        let mut stack_len = 0;
+        let mut current = 0usize;
+
+        loop {
+            let node = &self.nodes[current];
+            if node.bbox.hit(r, Interval::new(ray_t.min, closest_so_far)) {
+                match node.kind {
+                    NodeKind::Leaf { first, count } => {
+                        let first = first as usize;
+                        if let Some(t) = hit_leaf(first..first + count as usize, Interval::new(ray_t.min, closest_so_far)) {
+                            hit_anything = true;
+                            closest_so_far = t;
+                        }
+                    }
+                    NodeKind::Interior { second_child, axis } => {
+                        // Visit the child on the near side of the split first
+                        let (near, far) = if dir_is_neg[axis as usize] {
+                            (second_child, current as u32 + 1)
+                        } else {
+                            (current as u32 + 1, second_child)
+                        };
+                        stack[stack_len] = far;
+                        stack_len += 1;
+                        current = near as usize;
+                        continue;
+                    }
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len] as usize;
+        }
+
+        hit_anything
+    }
+}
+
+// State shared by every level of the recursive build
+struct Builder<'b, F> {
+    nodes: Vec<LinearNode>,
+    object_box: &'b F,
+    method: SplitMethod,
+    stats: BuildStats,
+}
+
+impl<F> Builder<'_, F> {
+    fn build<T: Clone>(&mut self, objects: &mut [T], start: usize, end: usize, depth: usize) -> usize
+    where
+        F: Fn(&T) -> AABB,
+    {
+        let object_box = self.object_box;
+        let mut bbox = AABB::empty();
+        for object in &objects[start..end] {
+            bbox = AABB::from_two_boxes(bbox, object_box(object));
+        }
+
+        let span = end - start;
+        let index = self.nodes.len();
+        let area = bbox.surface_area();
+
+        self.stats.nodes += 1;
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+
+        // Pick a split, or None to make a leaf
+        let split = match self.method {
+            _ if span == 1 => None,
+            SplitMethod::Median if span <= 2 => None,
+            SplitMethod::Median => {
+                bvh::median_sort(&mut objects[start..end], bbox.longest_axis(), object_box);
+                Some((start + span / 2, bbox.longest_axis()))
+            }
+            SplitMethod::Sah { .. } if depth >= MAX_SAH_DEPTH => {
+                bvh::median_sort(&mut objects[start..end], bbox.longest_axis(), object_box);
+                Some((start + span / 2, bbox.longest_axis()))
+            }
+            SplitMethod::Sah { bins } => {
+                let axis = bbox.longest_axis();
+                match bvh::sah_split(&mut objects[start..end], bins, object_box) {
+                    Some((count, split_axis, cost)) => {
+                        let split_cost = TRAVERSAL_COST + INTERSECTION_COST * cost / area;
+                        let leaf_cost = INTERSECTION_COST * span as f64;
+                        if span <= MAX_LEAF_SIZE && leaf_cost <= split_cost {
+                            None
+                        } else {
+                            Some((start + count, split_axis))
+                        }
+                    }
+                    // All centroids coincide: nothing to gain, but keep leaves small
+                    None if span <= MAX_LEAF_SIZE => None,
+                    None => {
+                        bvh::median_sort(&mut objects[start..end], axis, object_box);
+                        Some((start + span / 2, axis))
+                    }
+                }
+            }
+        };
+
+        match split {
+            None => {
+                self.stats.leaves += 1;
+                self.stats.sah_cost += area * INTERSECTION_COST * span as f64;
+                self.nodes.push(LinearNode { bbox, kind: NodeKind::Leaf { first: start as u32, count: span as u32 } });
+            }
+            Some((mid, axis)) => {
+                self.stats.sah_cost += area * TRAVERSAL_COST;
+                self.nodes.push(LinearNode { bbox, kind: NodeKind::Interior { second_child: 0, axis: axis as u8 } });
+                self.build(objects, start, mid, depth + 1);
+                let second = self.build(objects, mid, end, depth + 1);
+                self.nodes[index].kind = NodeKind::Interior { second_child: second as u32, axis: axis as u8 };
+            }
+        }
+
+        index
+    }
+}
+
+pub struct LinearBVH {
+    tree: FlatTree,
+    primitives: Vec<HittablePtr>,
+}
+
+impl LinearBVH {
+    pub fn new(list: &HittableList) -> Self {
+        Self::with_method(list, SplitMethod::SAH)
+    }
+
+    pub fn with_method(list: &HittableList, method: SplitMethod) -> Self {
+        Self::build(list, method).0
+    }
+
+    // Build and report how long it took and how good the tree is. An empty list gives an
+    // empty BVH that nothing hits.
+    pub fn build(list: &HittableList, method: SplitMethod) -> (Self, BuildStats) {
+        let mut primitives = list.objects().clone();
+        let (tree, stats) = FlatTree::build(&mut primitives, method, &|o: &HittablePtr| o.bounding_box());
+        (Self { tree, primitives }, stats)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.tree.node_count()
+    }
+}
+
+impl Hittable for LinearBVH {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        self.tree.hit(r, ray_t, |range, mut interval| {
+            let mut closest = None;
+            for object in &self.primitives[range] {
+                if object.hit(r, interval, rec) {
+                    closest = Some(rec.t);
+                    interval.max = rec.t;
+                }
+            }
+            closest
+        })
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.tree.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::utils::prelude::random_f64_range;
+    use crate::vec3::{Color, Point3, Vec3};
+    use std::sync::Arc;
+
+    fn sphere(center: Point3, radius: f64) -> Box<dyn Hittable> {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Box::new(Sphere::new(crate::ray::point_to_ray(center), radius, material))
+    }
+
+    // Closest hit distance, if any
+    fn closest(object: &dyn Hittable, r: &Ray) -> Option<f64> {
+        let mut rec = HitRecord::new();
+        object.hit(r, Interval::new(0.001, f64::INFINITY), &mut rec).then_some(rec.t)
+    }
+
+    fn assert_same_hits(list: &HittableList, bvh: &LinearBVH, rays: &[Ray]) {
+        for r in rays {
+            match (closest(list, r), closest(bvh, r)) {
+                (Some(expected), Some(t)) => assert!((expected - t).abs() < 1e-9, "{} != {} for {:?} {:?}", t, expected, r.origin(), r.direction()),
+                (expected, t) => assert_eq!(t, expected, "for {:?} {:?}", r.origin(), r.direction()),
+            }
+        }
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut list = HittableList::new();
+        for _ in 0..200 {
+            let center = Point3::new(random_f64_range(-10.0, 10.0), random_f64_range(-10.0, 10.0), random_f64_range(-10.0, 10.0));
+            list.add(sphere(center, random_f64_range(0.1, 1.5)));
+        }
+
+        let rays: Vec<Ray> = (0..2000)
+            .map(|_| Ray::new(Vec3::random_range(-15.0, 15.0), Vec3::random_unit_vector()))
+            .collect();
+
+        for method in [SplitMethod::Median, SplitMethod::SAH] {
+            let bvh = LinearBVH::with_method(&list, method);
+            assert_same_hits(&list, &bvh, &rays);
+        }
+    }
+
+    #[test]
+    fn empty_never_hits() {
+        let bvh = LinearBVH::new(&HittableList::new());
+        assert_eq!(bvh.node_count(), 0);
+        for _ in 0..100 {
+            let r = Ray::new(Vec3::random_range(-1.0, 1.0), Vec3::random_unit_vector());
+            assert_eq!(closest(&bvh, &r), None);
+        }
+    }
+
+    #[test]
+    fn nearest_hit_with_overlapping_children() {
+        // A row of overlapping spheres: every split along x leaves the two child boxes
+        // overlapping, so the far child can hold a hit nearer than the near child's
+        let mut list = HittableList::new();
+        for i in 0..16 {
+            list.add(sphere(Point3::new(i as f64 * 0.5, 0.0, 0.0), 1.0 + 0.1 * (i % 3) as f64));
+        }
+        let bvh = LinearBVH::with_method(&list, SplitMethod::Median);
+
+        // Along the row in both directions, so `dir_is_neg` picks each child order
+        let mut rays = Vec::new();
+        for k in 0..50 {
+            let offset = Vec3::new(0.0, k as f64 * 0.02 - 0.5, k as f64 * 0.015 - 0.3);
+            rays.push(Ray::new(Point3::new(20.0, 0.0, 0.0) + offset, Vec3::new(-1.0, 0.01, 0.0)));
+            rays.push(Ray::new(Point3::new(-20.0, 0.0, 0.0) + offset, Vec3::new(1.0, -0.01, 0.0)));
+            rays.push(Ray::new(Point3::new(3.7, 0.0, 0.0) + offset, Vec3::new(-1.0, 0.0, 0.02)));
+        }
+        assert_same_hits(&list, &bvh, &rays);
+    }
+
+    #[test]
+    fn sah_beats_median_on_skewed_input() {
+        // A dense cluster of small boxes plus a few far outliers: median splits cut the
+        // cluster by count and leave big, mostly empty nodes over it
+        let mut boxes = Vec::new();
+        for i in 0..1000 {
+            let (x, y, z) = ((i % 10) as f64 * 0.1, (i / 10 % 10) as f64 * 0.1, (i / 100) as f64 * 0.1);
+            boxes.push(AABB::extrema_box(Point3::new(x, y, z), Point3::new(x + 0.05, y + 0.05, z + 0.05)));
+        }
+        for i in 1..=20 {
+            let x = i as f64 * 10.0;
+            boxes.push(AABB::extrema_box(Point3::new(x, -5.0, -5.0), Point3::new(x + 0.5, 5.0, 5.0)));
+        }
+
+        let rays: Vec<Ray> = (0..2000)
+            .map(|_| Ray::new(Point3::new(random_f64_range(-1.0, 200.0), 20.0, random_f64_range(-1.0, 2.0)), Vec3::new(0.0, -1.0, 0.0)))
+            .collect();
+
+        // Primitive tests over all rays, visiting every leaf a ray reaches
+        let primitive_tests = |method: SplitMethod| {
+            let mut primitives = boxes.clone();
+            let (tree, stats) = FlatTree::build(&mut primitives, method, &|b: &AABB| *b);
+            let mut tests = 0;
+            for r in &rays {
+                tree.hit(r, Interval::new(0.0, f64::INFINITY), |range, _| {
+                    tests += range.len();
+                    None
+                });
+            }
+            (stats.sah_cost, tests)
+        };
+
+        let (median_cost, median_tests) = primitive_tests(SplitMethod::Median);
+        let (sah_cost, sah_tests) = primitive_tests(SplitMethod::SAH);
+        assert!(sah_cost < median_cost, "SAH cost {} vs median {}", sah_cost, median_cost);
+        assert!(sah_tests < median_tests, "SAH tests {} vs median {}", sah_tests, median_tests);
+    }
+}
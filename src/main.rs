@@ -1,494 +1,268 @@
-use std::sync::Arc;
-
-// Import modules
-use rusty_raytracing::utils::prelude::*;
-
-fn bouncing_spheres() {
-
-    // World
-    let mut world = HittableList::new();
+// main.rs
+// Command-line front end: pick a built-in scene, override the render settings and write the image.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use rusty_raytracing::bvh::SplitMethod;
+use rusty_raytracing::image_writer::{self, ExrPrecision, OutputFormat};
+use rusty_raytracing::scene_file;
+use rusty_raytracing::scenes;
+
+const USAGE: &str = "\
+Usage: rusty_raytracing [OPTIONS] [SCENE]
+       rusty_raytracing [OPTIONS] --file <SCENE.toml>
+
+Renders one of the built-in scenes (default: final_scene_preview) or a TOML scene file.
+Without --output the image is written to stdout as ASCII PPM, so `> image.ppm` works.
+
+Options:
+  -l, --list              List the built-in scenes and exit
+  -f, --file <PATH>       Load the scene from a TOML scene description
+  -o, --output <PATH>     Write the image to PATH; the format comes from the extension
+                          (png, jpg/jpeg, ppm, exr, hdr, pfm)
+  -w, --width <N>         Image width in pixels
+  -s, --spp <N>           Samples per pixel
+  -d, --depth <N>         Maximum ray bounce depth, a hard cap; Russian roulette ends
+                          most paths at random well before it
+  -t, --threads <N>       Number of render threads (default: one per core)
+  -q, --quality <N>       JPEG quality, 1-100 (default: 90)
+      --half              Write EXR as 16-bit half floats instead of 32-bit floats
+  -b, --bvh <METHOD>      BVH build: sah (default) or median
+  -h, --help              Print this help and exit";
+
+// Exit codes
+const EXIT_FAILURE: u8 = 1; // the render or the output failed
+const EXIT_USAGE: u8 = 2;   // bad command line
+
+#[derive(Default)]
+struct Options {
+    help: bool,
+    list: bool,
+    scene: Option<String>,
+    file: Option<PathBuf>,
+    output: Option<PathBuf>,
+    width: Option<u32>,
+    spp: Option<u32>,
+    depth: Option<u32>,
+    threads: Option<usize>,
+    quality: Option<u8>,
+    half: bool,
+    bvh: Option<SplitMethod>,
+}
 
-    // Ground
-    //let material_ground = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    let checkered_ground_texture = Arc::new(CheckerTexture::from_colors(0.32, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9)));
-    world.add(Box::new(Sphere::new(Ray::new(Point3::new(0.0, -1000.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 1000.0, Arc::new(Lambertian::from_texture(checkered_ground_texture)))));
+enum SceneSource {
+    BuiltIn(String, scenes::SceneFn),
+    File(PathBuf),
+}
 
-    // Spheres
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = random_f64();
-            let center:Ray = Ray::new(Point3::new(a as f64 + 0.9*random_f64(), 0.2, b as f64 + 0.9*random_f64()), Vec3::new(0.0, 0.0, 0.0));
-            if (center.origin() - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                let sphere_material: Arc<dyn Material>;
-                if choose_mat < 0.8 {
-                    // diffuse
-                    let albedo = Color::new(random_f64()*random_f64(), random_f64()*random_f64(), random_f64()*random_f64());
-                    sphere_material = Arc::new(Lambertian::new(albedo));
-                    let moving_center: Ray = Ray::new(center.origin(), Vec3::new(0.5, random_f64_range(0.0, 0.2), 0.0));
-                    world.add(Box::new(Sphere::new(moving_center, 0.2, sphere_material)));
-                } else if choose_mat < 0.95 {
-                    // metal
-                    let albedo = Color::new(random_f64_range(0.5, 1.0), random_f64_range(0.5, 1.0), random_f64_range(0.5, 1.0));
-                    let fuzz = random_f64_range(0.0, 0.5);
-                    sphere_material = Arc::new(Metal::new(albedo, fuzz));
-                    world.add(Box::new(Sphere::new(center, 0.2, sphere_material)));
-                } else {
-                    // glass
-                    sphere_material = Arc::new(Dielectric::new(1.5));
-                    world.add(Box::new(Sphere::new(center, 0.2, sphere_material)));
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut opts = Options::default();
+
+    while let Some(arg) = args.next() {
+        // Accept both `--flag value` and `--flag=value`
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((f, v)) if f.starts_with("--") => (f.to_string(), Some(v.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = || inline_value.clone().or_else(|| args.next()).ok_or(format!("missing value for `{}`", flag));
+
+        match flag.as_str() {
+            "-h" | "--help" => opts.help = true,
+            "-l" | "--list" => opts.list = true,
+            "-f" | "--file" => opts.file = Some(PathBuf::from(value()?)),
+            "-o" | "--output" => opts.output = Some(PathBuf::from(value()?)),
+            "-w" | "--width" => opts.width = Some(parse_positive(&flag, &value()?)?),
+            "-s" | "--spp" => opts.spp = Some(parse_positive(&flag, &value()?)?),
+            "-d" | "--depth" => opts.depth = Some(parse_positive(&flag, &value()?)?),
+            "-t" | "--threads" => opts.threads = Some(parse_positive(&flag, &value()?)?),
+            "-q" | "--quality" => {
+                let q: u8 = parse_positive(&flag, &value()?)?;
+                if q > 100 {
+                    return Err(format!("`{}` must be between 1 and 100, got {}", flag, q));
+                }
+                opts.quality = Some(q);
+            }
+            "--half" => opts.half = true,
+            "-b" | "--bvh" => {
+                opts.bvh = Some(match value()?.as_str() {
+                    "sah" => SplitMethod::SAH,
+                    "median" => SplitMethod::Median,
+                    other => return Err(format!("`{}` expects `sah` or `median`, got `{}`", flag, other)),
+                });
+            }
+            _ if flag.starts_with('-') => return Err(format!("unknown option `{}`", flag)),
+            _ => {
+                if let Some(scene) = &opts.scene {
+                    return Err(format!("more than one scene given (`{}` and `{}`)", scene, arg));
                 }
+                opts.scene = Some(arg);
             }
         }
     }
 
-    let material1 = Arc::new(Dielectric::new(1.5));
-    world.add(Box::new(Sphere::new(Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 1.0, material1)));
-
-    let material2 = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
-    world.add(Box::new(Sphere::new(Ray::new(Point3::new(-4.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 1.0, material2)));
-
-    let material3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Box::new(Sphere::new(Ray::new(Point3::new(4.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 1.0, material3)));
-
-
-    let bvh_root = BVHNode::new(&world);
-
-    // Camera
-    let mut camera = Camera::new();
-
-    camera.aspect_ratio = 1.0; //16.0 / 9.0;
-    camera.image_width = 600; //400;
-    camera.samples_per_pixel = 200; //50;
-    camera.max_depth = 50;
-    camera.vfov = 20.0;
-    camera.look_from = Point3::new(13.0, 2.0, 3.0);
-    camera.look_at = Point3::new(0.0, 0.0, 0.0);
-    camera.vup = Vec3::new(0.0, 1.0, 0.0);
-
-    camera.defocus_angle = 0.6; // degrees
-    camera.focus_distance = 10.0;
-
-    camera.background_color = Color::new(0.7, 0.8, 1.0); // light blue background
-
-    //camera.render(&world);
-    camera.render(&bvh_root);
-
-    //eprintln!("Image dimensions: {}x{}\n", camera.image_width, camera.image_height);
-}
-
-
-fn checkered_sphere(){
-    // World
-    let mut world = HittableList::new();
-
-    let checker = CheckerTexture::from_colors(0.32, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9));
-    world.add(Box::new(Sphere::new(Ray::new(Point3::new(0.0, -10.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 10.0, Arc::new(Lambertian::from_texture(Arc::new(checker.clone()))))));
-    world.add(Box::new(Sphere::new(Ray::new(Point3::new(0.0, 10.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 10.0, Arc::new(Lambertian::from_texture(Arc::new(checker.clone()))))));
-
-    // Camera
-    let mut camera = Camera::new();
-
-    camera.aspect_ratio = 16.0 / 9.0;
-    camera.image_width = 400;
-    camera.samples_per_pixel = 100;
-    camera.max_depth = 50;
-
-    camera.vfov = 20.0;
-    camera.look_from = Point3::new(13.0, 2.0, 3.0);
-    camera.look_at = Point3::new(0.0, 0.0, 0.0);
-    camera.vup = Vec3::new(0.0, 1.0, 0.0);
-
-    camera.background_color = Color::new(0.7, 0.8, 1.0);
-
-    camera.defocus_angle = 0.0; // degrees
-
-    //camera.render(&world);
-    camera.render(&world);
-}
-
-fn earth(){
-    // World
-    let mut world = HittableList::new();
-
-    let earth_texture: Arc<dyn Texture> = Arc::new(ImageTexture::new("textures/earthmap.jpg"));
-    let earth_surface = Arc::new(Lambertian::from_texture(earth_texture));
-    let globe = Sphere::new(Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 2.0, earth_surface);
-    world.add(Box::new(globe));
-
-    
-    // Camera
-    let mut camera = Camera::new();
-
-    camera.aspect_ratio = 16.0 / 9.0;
-    camera.image_width = 400;
-    camera.samples_per_pixel = 100;
-    camera.max_depth = 50;
-
-    camera.vfov = 20.0;
-    camera.look_from = Point3::new(0.0, 0.0, 12.0);
-    camera.look_at = Point3::new(0.0, 0.0, 0.0);
-    camera.vup = Vec3::new(0.0, 1.0, 0.0);
-
-    camera.background_color = Color::new(0.7, 0.8, 1.0);
-
-    camera.defocus_angle = 0.0; // degrees
-
-    //camera.render(&world);
-    camera.render(&world);
-}
-
-
-fn perlin_sphere(){
-    // World
-    let mut world = HittableList::new();
-
-    let perlin_texture: Arc<dyn Texture> = Arc::new(NoiseTexture::new(4.0));
-    let perlin_surface = Arc::new(Lambertian::from_texture(perlin_texture));
-    let perlin_globe = Sphere::new(Ray::new(Point3::new(0.0, -1000.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 1000.0, perlin_surface.clone());
-    let perlin_sphere = Sphere::new(Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 2.0, perlin_surface.clone());
-    world.add(Box::new(perlin_globe));
-    world.add(Box::new(perlin_sphere));
-
-    
-    // Camera
-    let mut camera = Camera::new();
-
-    camera.aspect_ratio = 16.0 / 9.0;
-    camera.image_width = 400;
-    camera.samples_per_pixel = 100;
-    camera.max_depth = 50;
-
-    camera.vfov = 20.0;
-    camera.look_from = Point3::new(13.0, 2.0, 3.0);
-    camera.look_at = Point3::new(0.0, 0.0, 0.0);
-    camera.vup = Vec3::new(0.0, 1.0, 0.0);
-
-    camera.defocus_angle = 0.0; // degrees
-
-    camera.background_color = Color::new(0.7, 0.8, 1.0);
-
-    //camera.render(&world);
-    camera.render(&world);
-
+    Ok(opts)
 }
 
-fn quads(){
-    // World
-    let mut world = HittableList::new();
-
-    let left_red = Arc::new(Lambertian::new(Color::new(1.0, 0.2, 0.2)));
-    let back_green = Arc::new(Lambertian::new(Color::new(0.2, 1.0, 0.2)));
-    let right_blue = Arc::new(Lambertian::new(Color::new(0.2, 0.2, 1.0)));
-    let upper_orange = Arc::new(Lambertian::new(Color::new(1.0, 0.5, 0.0)));
-    let lower_teal = Arc::new(Lambertian::new(Color::new(0.2, 0.8, 0.8)));
-
-    world.add(Box::new(Quad::new(Point3::new(-3.0, -2.0, 5.0), Vec3::new(0.0, 0.0, -4.0), Vec3::new(0.0, 4.0, 0.0), left_red)));
-    world.add(Box::new(Quad::new(Point3::new(-2.0, -2.0, 0.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 0.0), back_green)));
-    world.add(Box::new(Quad::new(Point3::new(3.0, -2.0, 1.0), Vec3::new(0.0, 0.0, 4.0), Vec3::new(0.0, 4.0, 0.0), right_blue)));
-    world.add(Box::new(Quad::new(Point3::new(-2.0, 3.0, 1.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0 , 4.0), upper_orange)));
-    world.add(Box::new(Quad::new(Point3::new(-2.0, -3.0, 5.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0 , -4.0), lower_teal)));
-
-    
-    // Camera
-    let mut camera = Camera::new();
-
-    camera.aspect_ratio = 1.0;
-    camera.image_width = 600;
-    camera.samples_per_pixel = 200;
-    camera.max_depth = 50;
-
-    camera.vfov = 100.0;
-    camera.look_from = Point3::new(0.0, 0.0, 9.0);
-    camera.look_at = Point3::new(0.0, 0.0, -1.0);
-    camera.vup = Vec3::new(0.0, 1.0, 0.0);
-
-    camera.background_color = Color::new(0.7, 0.8, 1.0);
-
-    camera.defocus_angle = 0.0; // degrees
-
-    //camera.render(&world);
-    camera.render(&world);
-
-}
-
-fn simple_light(){
-    // World
-    let mut world = HittableList::new();
-
-    let perlin_texture: Arc<dyn Texture> = Arc::new(NoiseTexture::new(4.0));
-    let perlin_surface = Arc::new(Lambertian::from_texture(perlin_texture));
-    let perlin_globe = Sphere::new(Ray::new(Point3::new(0.0, -1000.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 1000.0, perlin_surface.clone());
-    let perlin_sphere = Sphere::new(Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 2.0, perlin_surface.clone());
-    world.add(Box::new(perlin_globe));
-    world.add(Box::new(perlin_sphere));
-
-    let difflight = Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
-    world.add(Box::new(Quad::new(Point3::new(3.0, 1.0, -2.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), difflight.clone())));
-    
-    let sphere_light = Sphere::new(Ray::new(Point3::new(0.0, 7.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 2.0, difflight.clone());
-    world.add(Box::new(sphere_light));
-    
-    // Camera
-    let mut camera = Camera::new();
-
-    camera.aspect_ratio = 16.0 / 9.0;
-    camera.image_width = 600;
-    camera.samples_per_pixel = 200;
-    camera.max_depth = 50;
-
-    camera.vfov = 20.0;
-    camera.look_from = Point3::new(26.0, 3.0, 6.0);
-    camera.look_at = Point3::new(0.0, 2.0, 0.0);
-    camera.vup = Vec3::new(0.0, 1.0, 0.0);
-
-    camera.defocus_angle = 0.0; // degrees
-
-    camera.background_color = Color::new(0.0, 0.0, 0.0);
-    //camera.background_color = Color::new(0.7, 0.8, 1.0);
-
-    //camera.render(&world);
-    camera.render(&world);
+fn parse_positive<T: FromStr + PartialOrd + Default>(flag: &str, value: &str) -> Result<T, String> {
+    match value.parse::<T>() {
+        Ok(n) if n > T::default() => Ok(n),
+        _ => Err(format!("`{}` expects a positive integer, got `{}`", flag, value)),
+    }
 }
 
-
-fn conrell_box(){
-    // World
-    let mut world = HittableList::new();
-
-    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
-    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
-    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
-    let light = Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
-
-    world.add(Box::new(Quad::new(Point3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), Vec3::new(0.0, 555.0, 0.0), green))); // left
-    world.add(Box::new(Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(0.0, 0.0, -555.0), Vec3::new(0.0, 555.0, 0.0), red))); // right
-    world.add(Box::new(Quad::new(Point3::new(0.0, 555.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone()))); // floor
-    world.add(Box::new(Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -555.0), white.clone()))); // floor
-    world.add(Box::new(Quad::new(Point3::new(555.0, 0.0, 555.0), Vec3::new(-555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white.clone()))); // back
-
-    // light
-    world.add(Box::new(Quad::new(Point3::new(213.0, 554.0, 227.0), Vec3::new(130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 105.0), light))); // light
-
-    // box 1
-    let box1 = Quad::make_box(&Point3::new(0.0, 0.0, 0.0), &Point3::new(165.0, 330.0, 165.0), white.clone());
-    let box1 = RotateY::new(Arc::new(box1), 15.0);
-    let box1 = Translate::new(Arc::new(box1), Vec3::new(265.0, 0.0, 295.0));
-
-    // box 2
-    let box2 = Quad::make_box(&Point3::new(0.0, 0.0, 0.0), &Point3::new(165.0, 165.0, 165.0), white.clone());
-    let box2 = RotateY::new(Arc::new(box2), -18.0);
-    let box2 = Translate::new(Arc::new(box2), Vec3::new(130.0, 0.0, 65.0));
-
-    world.add(Box::new(box1));
-    world.add(Box::new(box2));
-
-    // Camera
-    let mut camera = Camera::new();
-
-    camera.aspect_ratio = 1.0;
-    camera.image_width = 600;
-    camera.samples_per_pixel = 200;
-    camera.max_depth = 30;
-
-    camera.vfov = 40.0;
-    camera.look_from = Point3::new(278.0, 278.0, -800.0);
-    camera.look_at = Point3::new(278.0, 278.0, 0.0);
-    camera.vup = Vec3::new(0.0, 1.0, 0.0);
-
-    camera.defocus_angle = 0.0; // degrees
-
-    camera.background_color = Color::new(0.0, 0.0, 0.0);
-    //camera.background_color = Color::new(0.7, 0.8, 1.0);
-
-    //camera.render(&world);
-    camera.render(&world);
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    ExitCode::from(EXIT_USAGE)
 }
 
-fn cornell_smoke(){
-    // World
-    let mut world = HittableList::new();
+fn main() -> ExitCode {
+    let opts = match parse_args(std::env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(message) => return usage_error(&message),
+    };
 
-    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
-    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
-    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
-    let light = Arc::new(DiffuseLight::new(Color::new(7.0, 7.0, 7.0)));
-
-    world.add(Box::new(Quad::new(Point3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green))); // left
-    world.add(Box::new(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red))); // right
-    world.add(Box::new(Quad::new(Point3::new(113.0, 554.0, 127.0), Vec3::new(330.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 305.0), light))); // light
-    world.add(Box::new(Quad::new(Point3::new(0.0, 555.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone()))); // floor
-    world.add(Box::new(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone()))); // floor
-    world.add(Box::new(Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white.clone()))); // back
-
-    // add boxes
-    let box1 = Quad::make_box(&Point3::new(0.0, 0.0, 0.0), &Point3::new(165.0, 330.0, 165.0), white.clone());
-    let box1 = RotateY::new(Arc::new(box1), 15.0);
-    let box1 = Translate::new(Arc::new(box1), Vec3::new(265.0, 0.0, 295.0));
-    let smoke_box1 = constant_medium::from_color(Arc::new(box1), 0.01, &Color::new(0.0, 0.0, 0.0));
-
-    let box2 = Quad::make_box(&Point3::new(0.0, 0.0, 0.0), &Point3::new(165.0, 165.0, 165.0), white.clone());
-    let box2 = RotateY::new(Arc::new(box2), -18.0);
-    let box2 = Translate::new(Arc::new(box2), Vec3::new(130.0, 0.0, 65.0));
-    let smoke_box2 = constant_medium::from_color(Arc::new(box2), 0.01, &Color::new(1.0, 1.0, 1.0));
-
-    world.add(Box::new(smoke_box1));
-    world.add(Box::new(smoke_box2));
-
-    // Camera
-    let mut camera = Camera::new();
+    if opts.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
 
-    camera.aspect_ratio = 1.0;
-    camera.image_width = 600;//600;
-    camera.samples_per_pixel = 200;//200;
-    camera.max_depth = 50;
+    if opts.list {
+        for (name, description, _) in scenes::SCENES {
+            println!("{:<22}{}", name, description);
+        }
+        return ExitCode::SUCCESS;
+    }
 
-    camera.vfov = 40.0;
-    camera.look_from = Point3::new(278.0, 278.0, -800.0);
-    camera.look_at = Point3::new(278.0, 278.0, 0.0);
-    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+    let source = match (&opts.file, &opts.scene) {
+        (Some(_), Some(_)) => return usage_error("give either a built-in scene or --file, not both"),
+        (Some(path), None) => SceneSource::File(path.clone()),
+        (None, name) => {
+            let name = name.as_deref().unwrap_or(scenes::DEFAULT_SCENE);
+            match scenes::find(name) {
+                Some(make_scene) => SceneSource::BuiltIn(name.to_string(), make_scene),
+                None => return usage_error(&format!("unknown scene `{}` (use --list to see the built-in scenes)", name)),
+            }
+        }
+    };
+
+    // Check the output format up front so a typo doesn't cost a whole render
+    let format = match &opts.output {
+        Some(path) => match OutputFormat::from_path(path) {
+            Some(OutputFormat::Jpeg { quality }) => Some(OutputFormat::Jpeg { quality: opts.quality.unwrap_or(quality) }),
+            Some(OutputFormat::Exr { .. }) if opts.half => Some(OutputFormat::Exr { precision: ExrPrecision::Half }),
+            Some(format) => Some(format),
+            None => return usage_error(&format!("cannot tell the image format of `{}` from its extension", path.display())),
+        },
+        None => None,
+    };
+    // Options that only mean something for one format are mistakes anywhere else
+    if opts.quality.is_some() && !matches!(format, Some(OutputFormat::Jpeg { .. })) {
+        return usage_error("`--quality` only applies to JPEG output");
+    }
+    if opts.half && !matches!(format, Some(OutputFormat::Exr { .. })) {
+        return usage_error("`--half` only applies to EXR output");
+    }
 
-    camera.defocus_angle = 0.0; // degrees
+    if let Some(threads) = opts.threads
+        && let Err(e) = rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()
+    {
+        eprintln!("error: could not start {} render threads: {}", threads, e);
+        return ExitCode::from(EXIT_FAILURE);
+    }
 
-    camera.background_color = Color::new(0.0, 0.0, 0.0);
+    let mut scene = match &source {
+        SceneSource::BuiltIn(_, make_scene) => make_scene(),
+        SceneSource::File(path) => match scene_file::load_scene(path) {
+            Ok(scene) => scene,
+            Err(e) => {
+                eprintln!("error: {}: {}", path.display(), e);
+                return ExitCode::from(EXIT_FAILURE);
+            }
+        },
+    };
+    if let Some(width) = opts.width { scene.camera.image_width = width; }
+    if let Some(spp) = opts.spp { scene.camera.samples_per_pixel = spp; }
+    if let Some(depth) = opts.depth { scene.camera.max_depth = depth; }
+    if let Some(bvh) = opts.bvh { scene.bvh = bvh; }
+
+    match &source {
+        SceneSource::BuiltIn(name, _) => eprintln!("Rendering scene `{}`", name),
+        SceneSource::File(path) => eprintln!("Rendering {}", path.display()),
+    }
+    let image = scene.render();
+
+    let written = match (&opts.output, format) {
+        (Some(path), Some(format)) => image_writer::save_with_format(&image, path, format).map_err(|e| e.to_string()),
+        _ => {
+            // Write PPM sequentially to stdout (so `> image.ppm` works)
+            let stdout = io::stdout();
+            let mut out = io::BufWriter::new(stdout.lock());
+            image.write_ppm(&mut out).and_then(|_| out.flush()).map_err(|e| e.to_string())
+        }
+    };
 
-    camera.render(&world);
+    match written {
+        Ok(()) => {
+            if let Some(path) = &opts.output {
+                eprintln!("Wrote {}", path.display());
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            let target = opts.output.as_ref().map_or("stdout".to_string(), |p| p.display().to_string());
+            eprintln!("error: failed to write {}: {}", target, e);
+            ExitCode::from(EXIT_FAILURE)
+        }
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn final_scene(image_width: u32, samples_per_pixel: u32, max_depth: u32){
-    //let mut boxes1 = HittableList::new();
-
-    let mut world = HittableList::new();
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
 
-    let ground = Arc::new(Lambertian::new(Color::new(0.48, 0.83, 0.53)));
+    #[test]
+    fn flags_take_separate_or_inline_values() {
+        let opts = parse(&["--width=320", "-s", "16", "--bvh=median", "-o", "out.png", "cornell_box"]).unwrap();
+        assert_eq!(opts.width, Some(320));
+        assert_eq!(opts.spp, Some(16));
+        assert_eq!(opts.bvh, Some(SplitMethod::Median));
+        assert_eq!(opts.output, Some(PathBuf::from("out.png")));
+        assert_eq!(opts.scene.as_deref(), Some("cornell_box"));
+    }
 
-    let boxes_per_side = 20;
-    for i in 0..boxes_per_side {
-        for j in 0..boxes_per_side{
-            let w = 100.0;
-            let x0 = -1000.0 + i as f64 * w;
-            let z0 = -1000.0 + j as f64 * w;
-            let y0 = 0.0;
-            let x1 = x0 + w;
-            let y1 = random_f64_range(1.0, 101.0);
-            let z1 = z0 + w;
+    #[test]
+    fn missing_value_is_an_error() {
+        let err = parse(&["cornell_box", "--spp"]).err().unwrap();
+        assert!(err.contains("missing value for `--spp`"), "{}", err);
+    }
 
-            world.add(Box::new(Quad::make_box(&Point3::new(x0, y0, z0), &Point3::new(x1, y1, z1), ground.clone())));
+    #[test]
+    fn numbers_must_be_positive() {
+        for args in [["--width", "0"], ["--depth", "-3"], ["--threads", "many"]] {
+            let err = parse(&args).err().unwrap();
+            assert!(err.contains("expects a positive integer"), "{}", err);
         }
+        assert_eq!(parse_positive::<u32>("-w", "7"), Ok(7));
     }
 
-
-    let light = Arc::new(DiffuseLight::new(Color::new(7.0, 7.0, 7.0)));
-    world.add(Box::new(Quad::new(Point3::new(123.0, 554.0, 147.0), Vec3::new(300.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 265.0), light.clone()))); // light
-
-    // Moving sphere
-    let center = Point3::new(400.0, 400.0, 200.0);
-    let direction = Vec3::new(30.0, 0.0, 0.0);
-    let moving_sphere_material = Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.1)));
-    world.add(Box::new(Sphere::new(Ray::new(center, direction), 50.0, moving_sphere_material.clone())));
-
-    // dielectric (glass) sphere
-    let pos1 = Ray::new(Point3::new(260.0, 150.0, 45.0), Vec3::init_zero());
-    world.add(Box::new(Sphere::new(pos1, 50.0, Arc::new(Dielectric::new(1.5)))));
-
-    // metal sphere
-    let pos2 = Ray::new(Point3::new(0.0, 150.0, 145.0), Vec3::init_zero());
-    let albedo = Color::new(0.8, 0.8, 0.9);
-    let fuzz = 0.8;
-    let sphere_material = Arc::new(Metal::new(albedo, fuzz));
-    world.add(Box::new(Sphere::new(pos2, 50.0, sphere_material)));
-
-    
-    // blue glass ball with smoke inside
-    let boundary = Sphere::new(Ray::new(Point3::new(360.0, 150.0, 145.0), Vec3::init_zero()), 70.0, Arc::new(Dielectric::new(1.5)));
-    world.add(Box::new(boundary.clone()));
-    let medium1 = constant_medium::from_color(Arc::new(boundary.clone()), 0.2, &Color::new(0.2, 0.4, 0.9));
-    world.add(Box::new(medium1));
-
-
-    // large sphere with low-density smoke cowering entire scene, creating a foggy atmosphere
-    let boundary2 = Sphere::new(Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::init_zero()), 5000.0, Arc::new(Dielectric::new(1.5)));
-    let medium2 = constant_medium::from_color(Arc::new(boundary2.clone()), 0.0001, &Color::new(1.0, 1.0, 1.0));
-    world.add(Box::new(medium2));  
-
-    // Earth sphere with texture mapping
-    let earth_texture: Arc<dyn Texture> = Arc::new(ImageTexture::new("textures/earthmap.jpg"));
-    let earth_material = Arc::new(Lambertian::from_texture(earth_texture));
-    world.add(Box::new(Sphere::new(Ray::new(Point3::new(400.0, 200.0, 400.0), Vec3::init_zero()), 100.0, earth_material.clone())));
-    
-    // Perlin noise textured sphere
-    // this might be slowing down the rendering significantly, especially at higher resolutions and sample counts
-    let pertex = Arc::new(NoiseTexture::new(0.2));
-    world.add(Box::new(Sphere::new(Ray::new(Point3::new(220.0, 280.0, 300.0), Vec3::init_zero()), 80.0, Arc::new(Lambertian::from_texture(pertex)))));
-
-    // Cluster of small spheres
-    let mut boxes2 = HittableList::new();
-
-    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
-
-    let ns = 1000;
-    for _ in 0..ns {
-        let random_position = Point3::new(
-            random_f64_range(0.0, 165.0),
-            random_f64_range(0.0, 165.0),
-            random_f64_range(0.0, 165.0),
-        );
-        boxes2.add(Box::new(Sphere::new(
-            Ray::new(random_position, Vec3::init_zero()),
-            10.0,
-            white.clone(),
-        )));
+    #[test]
+    fn quality_must_be_in_range() {
+        assert_eq!(parse(&["-q", "100"]).unwrap().quality, Some(100));
+        assert!(parse(&["-q", "101"]).err().unwrap().contains("between 1 and 100"));
+        assert!(parse(&["-q", "0"]).is_err());
+        assert!(parse(&["--quality=300"]).is_err());
     }
 
-    // Build a BVH for the cluster BEFORE rotating/translating it
-    let boxes2_bvh = Arc::new(BVHNode::new(&boxes2));
-
-    world.add(Box::new(
-        Translate::new(
-            Arc::new(RotateY::new(boxes2_bvh, 15.0)),
-            Vec3::new(-100.0, 270.0, 395.0),
-        )
-    ));
-
-    // Camera
-    let mut camera = Camera::new();
-
-    camera.aspect_ratio = 1.0;
-    camera.image_width = image_width;
-    camera.samples_per_pixel = samples_per_pixel;
-    camera.max_depth = max_depth;
-
-    camera.vfov = 40.0;
-    camera.look_from = Point3::new(478.0, 278.0, -600.0);
-    camera.look_at = Point3::new(278.0, 278.0, 0.0);
-    camera.vup = Vec3::new(0.0, 1.0, 0.0);
-
-    camera.background_color = Color::new(0.0, 0.0, 0.0);
-
-    camera.defocus_angle = 0.0; // degrees
-
-    let root = BVHNode::new(&world); // create BVH for the entire scene
-    //camera.render(&world);
-    camera.render(&root);
-
-}
-
-
-
-fn main() {
-    let option = 0;
-
-    match option {
-        1 => bouncing_spheres(),
-        2 => checkered_sphere(),
-        3 => earth(),
-        4 => perlin_sphere(),
-        5 => quads(),
-        6 => simple_light(),
-        7 => conrell_box(),
-        8 => cornell_smoke(),
-        9 => final_scene(800, 10000, 40), // took over an hour
-        _ => { eprintln!("running scene default\n");
-            final_scene(400, 500, 10);} // ~ less than a minute
+    #[test]
+    fn only_one_scene() {
+        let err = parse(&["cornell_box", "final_scene"]).err().unwrap();
+        assert!(err.contains("more than one scene"), "{}", err);
     }
 }
@@ -2,23 +2,75 @@
 
 use crate::vec3::{Color, Vec3, Point3};
 use crate::hittable::{HitRecord};
+use crate::medium::{AbsorbingMedium, Medium};
 use crate::ray::Ray;
-use crate::utils::prelude::{random_f64};
+use crate::utils::prelude::{random_f64, PI};
 use crate::texture::{Texture, SolidColor, CheckerTexture};
+use crate::microfacet::{self, GGX};
+use crate::onb::ONB;
+use crate::spectrum::{self, Ior};
 use std::sync::Arc;
 
 
+// One sampled bounce: the new direction, the path throughput along it (bsdf * cos / pdf,
+// or just the attenuation for a specular lobe) and the pdf it was picked with
+#[derive(Copy, Clone, Debug)]
+pub struct ScatterRecord {
+    pub direction: Vec3,
+    pub throughput: Color,
+    pub pdf: f64, // per solid angle; 0 for specular lobes
+    pub is_specular: bool,
+    pub wavelength: Option<f64>, // set when this bounce split the path off into one wavelength
+}
+
+impl ScatterRecord {
+    pub fn specular(direction: Vec3, throughput: Color) -> Self {
+        Self { direction, throughput, pdf: 0.0, is_specular: true, wavelength: None }
+    }
+}
+
+// Directions follow the usual convention: wo points back along the incoming ray (toward the
+// viewer), wi is the direction light arrives from (where the path continues). Both are unit
+// vectors. eval() includes the cosine term for surfaces; phase functions have none.
 pub trait Material: Send + Sync {
-    fn scatter(&self,
-               r_in: &Ray,
-               rec: &HitRecord, 
-               attenuation: &mut Color, 
-               scattered: &mut Ray
-            ) -> bool;
+    // Pick a direction to continue the path in; None if the ray is absorbed
+    fn sample(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<ScatterRecord> {
+        None
+    }
 
-    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+    // bsdf * |cos(wi)| for a pair of directions; zero for specular lobes
+    fn eval(&self, _rec: &HitRecord, _wi: Vec3, _wo: Vec3) -> Color {
         Color::init_zero()
     }
+
+    // Density with which sample() picks wi given wo
+    fn pdf(&self, _rec: &HitRecord, _wi: Vec3, _wo: Vec3) -> f64 {
+        0.0
+    }
+
+    // Only scatters into discrete directions, so lights can't be sampled for it
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    // A phase function inside a participating medium rather than a surface
+    fn is_volume(&self) -> bool {
+        false
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        Color::init_zero()
+    }
+
+    // The medium filling the inside of surfaces made of this, if any (see MediumInterface)
+    fn medium(&self) -> Option<&dyn Medium> {
+        None
+    }
+
+    // Not really there: the surface only marks where a medium starts, and rays go straight on
+    fn is_invisible(&self) -> bool {
+        false
+    }
 }
 
 
@@ -37,6 +89,39 @@ impl Lambertian {
     }
 }
 
+// Oren-Nayar rough diffuse material: microfacets too rough to see, which backscatter and
+// flatten the falloff toward grazing angles (clay, concrete, the moon). sigma is the standard
+// deviation of the facet slopes in radians; zero is Lambertian.
+pub struct OrenNayar {
+    albedo: Arc<dyn Texture>,
+    sigma: Arc<dyn Texture>,
+}
+
+impl OrenNayar {
+    // Facet slope deviation for from_texture: about 20 degrees, a matte clay look
+    pub const DEFAULT_SIGMA: f64 = 0.35;
+
+    pub fn new(albedo: Color, sigma: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo))).with_sigma(sigma)
+    }
+
+    // Same arguments as Lambertian::from_texture, so loaders can use either
+    pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
+        let sigma = Self::DEFAULT_SIGMA;
+        Self::from_textures(albedo, Arc::new(SolidColor::from_rgb(sigma, sigma, sigma)))
+    }
+
+    // Roughness map read with Texture::scalar
+    pub fn from_textures(albedo: Arc<dyn Texture>, sigma: Arc<dyn Texture>) -> Self {
+        Self { albedo, sigma }
+    }
+
+    pub fn with_sigma(mut self, sigma: f64) -> Self {
+        self.sigma = Arc::new(SolidColor::from_rgb(sigma, sigma, sigma));
+        self
+    }
+}
+
 // Metal material
 pub struct Metal {
     albedo: Color,
@@ -50,14 +135,92 @@ impl Metal {
     }
 }
 
-// Dielectric material
+// Rough conductor: GGX microfacets with Fresnel from the metal's complex index of refraction.
+// Roughness is perceptual (alpha = roughness^2) and can differ along the two tangent
+// directions for brushed looks.
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    roughness_u: Arc<dyn Texture>,
+    roughness_v: Arc<dyn Texture>,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Self {
+        Self::anisotropic(eta, k, roughness, roughness)
+    }
+
+    pub fn anisotropic(eta: Color, k: Color, roughness_u: f64, roughness_v: f64) -> Self {
+        let u: Arc<dyn Texture> = Arc::new(SolidColor::from_rgb(roughness_u, roughness_u, roughness_u));
+        let v: Arc<dyn Texture> = Arc::new(SolidColor::from_rgb(roughness_v, roughness_v, roughness_v));
+        Self::from_textures(eta, k, u, v)
+    }
+
+    // Roughness maps are read with Texture::scalar
+    pub fn from_textures(eta: Color, k: Color, roughness_u: Arc<dyn Texture>, roughness_v: Arc<dyn Texture>) -> Self {
+        Self { eta, k, roughness_u, roughness_v }
+    }
+
+    // Replace the roughness, e.g. of a preset, with roughness maps
+    pub fn with_roughness_textures(mut self, roughness_u: Arc<dyn Texture>, roughness_v: Arc<dyn Texture>) -> Self {
+        self.roughness_u = roughness_u;
+        self.roughness_v = roughness_v;
+        self
+    }
+
+    // RGB-fitted measured indices
+    pub fn gold(roughness: f64) -> Self {
+        Self::new(Color::new(0.143119, 0.374957, 1.44248), Color::new(3.98316, 2.38572, 1.60322), roughness)
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Self::new(Color::new(0.155265, 0.116723, 0.138342), Color::new(4.82835, 3.12225, 2.14696), roughness)
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::new(Color::new(0.200438, 0.924033, 1.10221), Color::new(3.91295, 2.45285, 2.14219), roughness)
+    }
+
+    pub fn aluminium(roughness: f64) -> Self {
+        Self::new(Color::new(1.65746, 0.880369, 0.521229), Color::new(9.22387, 6.26952, 4.837), roughness)
+    }
+
+    fn distribution(&self, rec: &HitRecord) -> GGX {
+        let alpha_x = microfacet::roughness_to_alpha(self.roughness_u.scalar(rec.u, rec.v, &rec.p));
+        let alpha_y = microfacet::roughness_to_alpha(self.roughness_v.scalar(rec.u, rec.v, &rec.p));
+        GGX::new(alpha_x, alpha_y)
+    }
+}
+
+// Dielectric material. Colored glass absorbs through an AbsorbingMedium inside it, which the
+// camera's medium stack applies to every stretch of a path in the glass, up to whatever
+// it reaches next: the far side, an internal reflection or an object inside
 pub struct Dielectric {
-    refraction_index: f64,
+    ior: Ior,
+    interior: Option<AbsorbingMedium>, // None is clear glass
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f64) -> Self {
-        Self { refraction_index }
+        Self::from_ior(Ior::Constant(refraction_index))
+    }
+
+    // A wavelength-dependent index makes the glass disperse light into its colors
+    pub fn from_ior(ior: Ior) -> Self {
+        Self { ior, interior: None }
+    }
+
+    // Beer-Lambert coefficient per unit distance inside
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        let absorbs = absorption.x() > 0.0 || absorption.y() > 0.0 || absorption.z() > 0.0;
+        self.interior = absorbs.then(|| AbsorbingMedium::new(absorption));
+        self
+    }
+
+    // Colored glass: light keeps `transmittance` of its energy after travelling `distance`
+    // through the material, so thicker parts come out darker
+    pub fn with_transmittance(self, transmittance: Color, distance: f64) -> Self {
+        self.with_absorption(absorption_from_transmittance(transmittance, distance))
     }
 
     pub fn reflectance(&self, cosine: f64, ref_idx: f64) -> f64 {
@@ -68,11 +231,348 @@ impl Dielectric {
     }
 }
 
+// Rough dielectric: GGX microfacets that both reflect and transmit, with exact Fresnel.
+// Covers frosted glass and etched or translucent surfaces; zero roughness is plain glass.
+pub struct RoughDielectric {
+    refraction_index: f64,
+    roughness: Arc<dyn Texture>,
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f64, roughness: f64) -> Self {
+        Self::from_texture(refraction_index, Arc::new(SolidColor::from_rgb(roughness, roughness, roughness)))
+    }
+
+    // Roughness map read with Texture::scalar
+    pub fn from_texture(refraction_index: f64, roughness: Arc<dyn Texture>) -> Self {
+        Self { refraction_index, roughness }
+    }
+
+    fn distribution(&self, rec: &HitRecord) -> GGX {
+        let alpha = microfacet::roughness_to_alpha(self.roughness.scalar(rec.u, rec.v, &rec.p));
+        GGX::new(alpha, alpha)
+    }
+
+    // Index on the far side of the surface over the index on the side of the normal
+    fn relative_eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face { self.refraction_index } else { 1.0 / self.refraction_index }
+    }
+}
+
+// Principled (Disney-style) uber-material. A diffuse base with sheen, a GGX specular lobe that
+// blends from dielectric to metal, rough glass for transmission and a clearcoat on top, mixed
+// by the parameters below. Every parameter is a texture; the scalar ones are read with
+// Texture::scalar. new() gives a plastic-like default, then set the fields.
+pub struct Principled {
+    pub base_color: Arc<dyn Texture>,
+    pub metallic: Arc<dyn Texture>,
+    pub roughness: Arc<dyn Texture>,
+    pub specular: Arc<dyn Texture>, // 0.5 reflects 4% at normal incidence, like most dielectrics
+    pub specular_tint: Arc<dyn Texture>, // tints the dielectric specular toward the base color
+    pub sheen: Arc<dyn Texture>, // extra grazing reflection, for cloth
+    pub clearcoat: Arc<dyn Texture>,
+    pub transmission: Arc<dyn Texture>,
+    pub emission: Arc<dyn Texture>,
+    pub ior: f64, // of the transmissive part
+}
+
+// Roughness of the clearcoat layer, a fixed glossy varnish
+const CLEARCOAT_ROUGHNESS: f64 = 0.1;
+
+impl Principled {
+    pub fn new(base_color: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(base_color)))
+    }
+
+    pub fn from_texture(base_color: Arc<dyn Texture>) -> Self {
+        Self {
+            base_color,
+            metallic: Self::constant(0.0),
+            roughness: Self::constant(0.5),
+            specular: Self::constant(0.5),
+            specular_tint: Self::constant(0.0),
+            sheen: Self::constant(0.0),
+            clearcoat: Self::constant(0.0),
+            transmission: Self::constant(0.0),
+            emission: Self::constant(0.0),
+            ior: 1.5,
+        }
+    }
+
+    // Untextured value for one of the parameters
+    pub fn constant(x: f64) -> Arc<dyn Texture> {
+        Arc::new(SolidColor::from_rgb(x, x, x))
+    }
+
+    // Evaluate the parameters at the hit point and work out the lobe weights
+    fn lobes(&self, rec: &HitRecord) -> PrincipledLobes {
+        let (u, v, p) = (rec.u, rec.v, &rec.p);
+        let base = self.base_color.value(u, v, p);
+        let metallic = self.metallic.scalar(u, v, p).clamp(0.0, 1.0);
+        let roughness = self.roughness.scalar(u, v, p).clamp(0.0, 1.0);
+        let specular = self.specular.scalar(u, v, p).max(0.0);
+        let specular_tint = self.specular_tint.scalar(u, v, p).clamp(0.0, 1.0);
+        let transmission = self.transmission.scalar(u, v, p).clamp(0.0, 1.0);
+
+        // Dielectric reflectance at normal incidence, optionally tinted, blending into the
+        // base color as the surface turns metallic
+        let lum = luminance(base);
+        let tint = if lum > 0.0 { base / lum } else { Color::new(1.0, 1.0, 1.0) };
+        let white = Color::new(1.0, 1.0, 1.0);
+        let dielectric_f0 = (white * (1.0 - specular_tint) + tint * specular_tint) * (0.08 * specular);
+        let f0 = dielectric_f0 * (1.0 - metallic) + base * metallic;
+
+        let alpha = microfacet::roughness_to_alpha(roughness);
+        let eta = if rec.front_face { self.ior } else { 1.0 / self.ior };
+
+        let mut diffuse = (1.0 - metallic) * (1.0 - transmission);
+        let mut specular = 1.0 - (1.0 - metallic) * transmission;
+        let mut transmission = (1.0 - metallic) * transmission;
+        let mut clearcoat = self.clearcoat.scalar(u, v, p).max(0.0);
+
+        // Inside a transmissive object the surface is just the glass interface on the way out
+        if !rec.front_face && transmission > 0.0 {
+            (diffuse, specular, transmission, clearcoat) = (0.0, 0.0, 1.0, 0.0);
+        }
+
+        // Pick lobes roughly by how much light they carry
+        let weights = [diffuse, specular * (0.25 + 0.75 * luminance(f0)), transmission, 0.25 * clearcoat];
+        let total: f64 = weights.iter().sum();
+        let probabilities = if total > 0.0 { weights.map(|w| w / total) } else { [0.0; 4] };
+
+        PrincipledLobes {
+            base,
+            f0,
+            roughness,
+            sheen: self.sheen.scalar(u, v, p).max(0.0),
+            ggx: GGX::new(alpha, alpha),
+            clearcoat_ggx: GGX::new(microfacet::roughness_to_alpha(CLEARCOAT_ROUGHNESS), microfacet::roughness_to_alpha(CLEARCOAT_ROUGHNESS)),
+            eta,
+            diffuse,
+            specular,
+            transmission,
+            clearcoat,
+            probabilities,
+        }
+    }
+}
+
+// The principled parameters at one hit point
+struct PrincipledLobes {
+    base: Color,
+    f0: Color, // specular reflectance at normal incidence
+    roughness: f64,
+    sheen: f64,
+    ggx: GGX,
+    clearcoat_ggx: GGX,
+    eta: f64,
+    // Lobe weights, and the probabilities of sampling diffuse, specular, transmission and
+    // clearcoat
+    diffuse: f64,
+    specular: f64,
+    transmission: f64,
+    clearcoat: f64,
+    probabilities: [f64; 4],
+}
+
+impl PrincipledLobes {
+    // bsdf * |cos(wi)| summed over the lobes, and the combined density of sampling wi, in the
+    // local frame with wo.z > 0
+    fn eval(&self, wi: Vec3, wo: Vec3) -> (Color, f64) {
+        let mut f = Color::init_zero();
+        let mut pdf = 0.0;
+        if wo.z() <= 0.0 {
+            return (f, pdf);
+        }
+
+        if wi.z() > 0.0 {
+            let h = (wi + wo).unit_vector();
+            let cos_d = wi.dot(h);
+
+            if self.diffuse > 0.0 {
+                // Disney diffuse: retro-reflection on rough surfaces, plus sheen at grazing angles
+                let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+                let fl = 1.0 + (fd90 - 1.0) * schlick_weight(wi.z());
+                let fv = 1.0 + (fd90 - 1.0) * schlick_weight(wo.z());
+                let diffuse = self.base * (fl * fv / PI);
+                let sheen = self.sheen * schlick_weight(cos_d);
+                f = f + (diffuse + Color::new(sheen, sheen, sheen)) * (self.diffuse * wi.z());
+                pdf += self.probabilities[0] * wi.z() / PI;
+            }
+
+            if self.specular > 0.0 {
+                let fresnel = self.f0 + (Color::new(1.0, 1.0, 1.0) - self.f0) * schlick_weight(wo.dot(h));
+                let g = &self.ggx;
+                f = f + fresnel * (self.specular * g.d(h) * g.g(wo, wi) / (4.0 * wo.z()));
+                pdf += self.probabilities[1] * g.visible_d(wo, h) / (4.0 * wo.dot(h));
+            }
+
+            if self.clearcoat > 0.0 {
+                let fresnel = 0.04 + 0.96 * schlick_weight(wo.dot(h));
+                let g = &self.clearcoat_ggx;
+                let c = self.clearcoat * fresnel * g.d(h) * g.g(wo, wi) / (4.0 * wo.z());
+                f = f + Color::new(c, c, c);
+                pdf += self.probabilities[3] * g.visible_d(wo, h) / (4.0 * wo.dot(h));
+            }
+        }
+
+        if self.transmission > 0.0 {
+            let (ft, pt) = microfacet::dielectric_eval(&self.ggx, self.eta, wi, wo);
+            // Light going through picks up the base color, half on the way in and half out
+            let tint = if wi.z() < 0.0 { sqrt_color(self.base) } else { Color::new(1.0, 1.0, 1.0) };
+            f = f + tint * (self.transmission * ft);
+            pdf += self.probabilities[2] * pt;
+        }
+
+        (f, pdf)
+    }
+
+    fn sample(&self, wo: Vec3) -> Option<Vec3> {
+        let mut u = random_f64();
+        let mut lobe = 0;
+        while lobe < 3 && u >= self.probabilities[lobe] {
+            u -= self.probabilities[lobe];
+            lobe += 1;
+        }
+
+        let wi = match lobe {
+            0 => (Vec3::new(0.0, 0.0, 1.0) + Vec3::random_unit_vector()).unit_vector(),
+            1 => microfacet::reflect(wo, self.ggx.sample_visible_normal(wo)),
+            2 => microfacet::dielectric_sample(&self.ggx, self.eta, wo)?,
+            _ => microfacet::reflect(wo, self.clearcoat_ggx.sample_visible_normal(wo)),
+        };
+        (wi.z() != 0.0).then_some(wi)
+    }
+}
+
+#[inline]
+fn luminance(c: Color) -> f64 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+// (1 - cos)^5 from Schlick's Fresnel approximation
+#[inline]
+fn schlick_weight(cos_theta: f64) -> f64 {
+    (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
+
+#[inline]
+fn sqrt_color(c: Color) -> Color {
+    Color::new(c.x().max(0.0).sqrt(), c.y().max(0.0).sqrt(), c.z().max(0.0).sqrt())
+}
+
+// A dielectric coat over any other material: varnish, car paint clearcoat, lacquer. The coat
+// reflects by its Fresnel term, smooth or GGX-rough, and the base sees what gets through,
+// darkened by the coat's absorption along the way in and out.
+pub struct Coated {
+    base: Arc<dyn Material>,
+    refraction_index: f64,
+    roughness: Arc<dyn Texture>,
+    absorption: Color, // per unit distance inside the coat
+    thickness: f64,
+}
+
+impl Coated {
+    pub fn new(base: Arc<dyn Material>, refraction_index: f64, roughness: f64) -> Self {
+        Self::from_texture(base, refraction_index, Arc::new(SolidColor::from_rgb(roughness, roughness, roughness)))
+    }
+
+    // Coat roughness map read with Texture::scalar
+    pub fn from_texture(base: Arc<dyn Material>, refraction_index: f64, roughness: Arc<dyn Texture>) -> Self {
+        Self { base, refraction_index, roughness, absorption: Color::init_zero(), thickness: 0.0 }
+    }
+
+    pub fn with_absorption(mut self, absorption: Color, thickness: f64) -> Self {
+        self.absorption = absorption;
+        self.thickness = thickness;
+        self
+    }
+
+    // Tinted coat: `tint` is what is left of light crossing it once, straight down
+    pub fn with_tint(self, tint: Color, thickness: f64) -> Self {
+        self.with_absorption(absorption_from_transmittance(tint, thickness), thickness)
+    }
+
+    fn distribution(&self, rec: &HitRecord) -> GGX {
+        let alpha = microfacet::roughness_to_alpha(self.roughness.scalar(rec.u, rec.v, &rec.p));
+        GGX::new(alpha, alpha)
+    }
+
+    // What the coat lets through to and back from the base: Fresnel transmission at both
+    // crossings and absorption along the refracted paths. Directions below the surface (a
+    // transmissive base) only cross once.
+    fn attenuation(&self, wi: Vec3, wo: Vec3) -> Color {
+        let mut transmittance = 1.0 - microfacet::fresnel_dielectric(wo.z(), self.refraction_index);
+        let mut distance = self.thickness / self.refracted_cos(wo.z());
+        if wi.z() > 0.0 {
+            transmittance *= 1.0 - microfacet::fresnel_dielectric(wi.z(), self.refraction_index);
+            distance += self.thickness / self.refracted_cos(wi.z());
+        }
+        Color::new(
+            transmittance * (-self.absorption.x() * distance).exp(),
+            transmittance * (-self.absorption.y() * distance).exp(),
+            transmittance * (-self.absorption.z() * distance).exp(),
+        )
+    }
+
+    // Cosine of a direction once refracted into the coat
+    fn refracted_cos(&self, cos_theta: f64) -> f64 {
+        let sin2 = (1.0 - cos_theta * cos_theta).max(0.0) / (self.refraction_index * self.refraction_index);
+        (1.0 - sin2).max(1e-4).sqrt()
+    }
+
+    // The coat's own reflection: bsdf * cos and the density of sampling it
+    fn coat_eval(&self, ggx: &GGX, wi: Vec3, wo: Vec3) -> (f64, f64) {
+        if ggx.is_smooth() || wi.z() <= 0.0 || wo.z() <= 0.0 {
+            return (0.0, 0.0);
+        }
+        let h = (wi + wo).unit_vector();
+        let f = microfacet::fresnel_dielectric(wo.dot(h), self.refraction_index);
+        (f * ggx.d(h) * ggx.g(wo, wi) / (4.0 * wo.z()), ggx.visible_d(wo, h) / (4.0 * wo.dot(h)))
+    }
+
+    // Chance of sampling the coat rather than the base
+    fn coat_probability(&self, wo: Vec3) -> f64 {
+        microfacet::fresnel_dielectric(wo.z(), self.refraction_index).clamp(0.05, 0.95)
+    }
+}
+
+// Blend of two materials by a scalar mask: 0 gives `a`, 1 gives `b`, values in between mix
+// them. Each bounce picks one of the two at random by the mask, so a black and white checker
+// alternates materials and a soft mask blends them.
+pub struct MixMaterial {
+    a: Arc<dyn Material>,
+    b: Arc<dyn Material>,
+    mask: Arc<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(a: Arc<dyn Material>, b: Arc<dyn Material>, amount: f64) -> Self {
+        Self::from_texture(a, b, Arc::new(SolidColor::from_rgb(amount, amount, amount)))
+    }
+
+    // Mask read with Texture::scalar
+    pub fn from_texture(a: Arc<dyn Material>, b: Arc<dyn Material>, mask: Arc<dyn Texture>) -> Self {
+        Self { a, b, mask }
+    }
+
+    fn weight(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        self.mask.scalar(u, v, p).clamp(0.0, 1.0)
+    }
+}
+
+// Absorption coefficient that leaves `transmittance` after `distance`
+fn absorption_from_transmittance(transmittance: Color, distance: f64) -> Color {
+    let sigma = |t: f64| if t >= 1.0 || distance <= 0.0 { 0.0 } else { -t.max(1e-6).ln() / distance };
+    Color::new(sigma(transmittance.x()), sigma(transmittance.y()), sigma(transmittance.z()))
+}
+
 
 // Material implementations
 impl Material for Lambertian {
 
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+    fn sample(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        // Cosine-weighted around the normal
         let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
 
         // Catch degenerate scatter direction
@@ -80,45 +580,406 @@ impl Material for Lambertian {
             scatter_direction = rec.normal;
         }
 
-        *scattered = Ray::new_time(rec.p, scatter_direction, r_in.time());
-        *attenuation = self.texture.value(rec.u, rec.v, &rec.p);
-        return true;
+        let direction = scatter_direction.unit_vector();
+        let pdf = rec.normal.dot(direction).max(0.0) / PI;
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        // albedo/pi * cos / (cos/pi)
+        let throughput = self.texture.value(rec.u, rec.v, &rec.p);
+        Some(ScatterRecord { direction, throughput, pdf, is_specular: false, wavelength: None })
+    }
+
+    fn eval(&self, rec: &HitRecord, wi: Vec3, _wo: Vec3) -> Color {
+        let cos_theta = rec.normal.dot(wi);
+        if cos_theta <= 0.0 {
+            return Color::init_zero();
+        }
+        self.texture.value(rec.u, rec.v, &rec.p) * (cos_theta / PI)
+    }
+
+    fn pdf(&self, rec: &HitRecord, wi: Vec3, _wo: Vec3) -> f64 {
+        rec.normal.dot(wi).max(0.0) / PI
+    }
+}
+impl Material for OrenNayar {
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        // Cosine-weighted like Lambertian, which is close enough to the real lobe
+        let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
+        if scatter_direction.near_zero() {
+            scatter_direction = rec.normal;
+        }
+
+        let direction = scatter_direction.unit_vector();
+        let pdf = rec.normal.dot(direction).max(0.0) / PI;
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        let throughput = self.eval(rec, direction, -r_in.direction().unit_vector()) / pdf;
+        Some(ScatterRecord { direction, throughput, pdf, is_specular: false, wavelength: None })
+    }
+
+    fn eval(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> Color {
+        let frame = ONB::new(rec.normal);
+        let (wi, wo) = (frame.to_local(wi), frame.to_local(wo));
+        if wi.z() <= 0.0 || wo.z() <= 0.0 {
+            return Color::init_zero();
+        }
+
+        let sigma = self.sigma.scalar(rec.u, rec.v, &rec.p).max(0.0);
+        let sigma2 = sigma * sigma;
+        let a = 1.0 - sigma2 / (2.0 * (sigma2 + 0.33));
+        let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+        // cos(phi_i - phi_o) from the projections onto the tangent plane
+        let sin_i = (1.0 - wi.z() * wi.z()).max(0.0).sqrt();
+        let sin_o = (1.0 - wo.z() * wo.z()).max(0.0).sqrt();
+        let max_cos = if sin_i > 1e-4 && sin_o > 1e-4 {
+            ((wi.x() * wo.x() + wi.y() * wo.y()) / (sin_i * sin_o)).max(0.0)
+        } else {
+            0.0
+        };
+
+        // alpha is the larger of the two polar angles, beta the smaller
+        let (sin_alpha, tan_beta) = if wi.z() > wo.z() { (sin_o, sin_i / wi.z()) } else { (sin_i, sin_o / wo.z()) };
+
+        self.albedo.value(rec.u, rec.v, &rec.p) * ((a + b * max_cos * sin_alpha * tan_beta) * wi.z() / PI)
+    }
+
+    fn pdf(&self, rec: &HitRecord, wi: Vec3, _wo: Vec3) -> f64 {
+        rec.normal.dot(wi).max(0.0) / PI
     }
 }
 
+
 impl Material for Metal {
 
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         let mut reflected = Vec3::reflect(r_in.direction(), rec.normal);
         // Add fuzziness to the reflection
         reflected = reflected.unit_vector() + (Vec3::random_in_unit_sphere() * self.fuzz);
-        *scattered = Ray::new_time(rec.p, reflected, r_in.time());
-        *attenuation = self.albedo;
-        return scattered.direction().dot(rec.normal) > 0.0;
+        // Fuzzed below the surface: absorbed
+        if reflected.dot(rec.normal) <= 0.0 {
+            return None;
+        }
+        Some(ScatterRecord::specular(reflected.unit_vector(), self.albedo))
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
+
+impl Material for Conductor {
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let frame = microfacet::shading_frame(rec.normal, rec.dpdu);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let ggx = self.distribution(rec);
+        if ggx.is_smooth() {
+            let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+            let f = microfacet::fresnel_conductor(wo.z(), self.eta, self.k);
+            return Some(ScatterRecord::specular(frame.transform(wi), f));
+        }
+
+        let h = ggx.sample_visible_normal(wo);
+        let wi = microfacet::reflect(wo, h);
+        // Shadowed by the microsurface: the single-scattering model loses this energy
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        // f * cos / pdf reduces to F * G / G1(wo) for visible-normal sampling
+        let f = microfacet::fresnel_conductor(wo.dot(h), self.eta, self.k);
+        let throughput = f * (ggx.g(wo, wi) / ggx.g1(wo));
+        let pdf = ggx.visible_d(wo, h) / (4.0 * wo.dot(h));
+        Some(ScatterRecord { direction: frame.transform(wi), throughput, pdf, is_specular: false, wavelength: None })
+    }
+
+    fn eval(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> Color {
+        let frame = microfacet::shading_frame(rec.normal, rec.dpdu);
+        let (wi, wo) = (frame.to_local(wi), frame.to_local(wo));
+        let ggx = self.distribution(rec);
+        if wi.z() <= 0.0 || wo.z() <= 0.0 || ggx.is_smooth() {
+            return Color::init_zero();
+        }
+
+        let h = (wi + wo).unit_vector();
+        let f = microfacet::fresnel_conductor(wo.dot(h), self.eta, self.k);
+        // D G F / (4 cos_o cos_i), times cos_i
+        f * (ggx.d(h) * ggx.g(wo, wi) / (4.0 * wo.z()))
+    }
+
+    fn pdf(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> f64 {
+        let frame = microfacet::shading_frame(rec.normal, rec.dpdu);
+        let (wi, wo) = (frame.to_local(wi), frame.to_local(wo));
+        let ggx = self.distribution(rec);
+        if wi.z() <= 0.0 || wo.z() <= 0.0 || ggx.is_smooth() {
+            return 0.0;
+        }
+
+        let h = (wi + wo).unit_vector();
+        ggx.visible_d(wo, h) / (4.0 * wo.dot(h))
     }
 }
 
 impl Material for Dielectric {
 
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
-        *attenuation = Color::new(1.0, 1.0, 1.0);
-        let refraction_index = if rec.front_face { 1.0 / self.refraction_index } else { self.refraction_index };
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        // A dispersive index needs a wavelength; rays still carrying RGB pick one here
+        let (ior, wavelength, tint) = match r_in.wavelength() {
+            _ if !self.ior.is_dispersive() => (self.ior.nominal(), None, Color::new(1.0, 1.0, 1.0)),
+            Some(lambda) => (self.ior.at(lambda), None, Color::new(1.0, 1.0, 1.0)),
+            None => {
+                let (lambda, weight) = spectrum::sample_wavelength();
+                (self.ior.at(lambda), Some(lambda), weight)
+            }
+        };
+        let refraction_index = if rec.front_face { 1.0 / ior } else { ior };
 
         let unit_direction = r_in.direction().unit_vector();
         let cos_theta = f64::min((-unit_direction).dot(rec.normal), 1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_index * sin_theta > 1.0;
-        let mut direction = Vec3::new(0.0, 0.0, 0.0);
 
-        if cannot_refract || self.reflectance(cos_theta, refraction_index) > random_f64() {
-            direction = Vec3::reflect(unit_direction, rec.normal);
-        }else{
-            direction = Vec3::refract(&unit_direction, &rec.normal, refraction_index);
+        let direction = if cannot_refract || self.reflectance(cos_theta, refraction_index) > random_f64() {
+            Vec3::reflect(unit_direction, rec.normal)
+        } else {
+            Vec3::refract(&unit_direction, &rec.normal, refraction_index)
+        };
+
+        Some(ScatterRecord { wavelength, ..ScatterRecord::specular(direction, tint) })
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    fn medium(&self) -> Option<&dyn Medium> {
+        self.interior.as_ref().map(|m| m as &dyn Medium)
+    }
+}
+
+impl Material for RoughDielectric {
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let frame = microfacet::shading_frame(rec.normal, rec.dpdu);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
         }
-       
-        *scattered = Ray::new_time(rec.p, direction, r_in.time());
-        return true;
+
+        let eta = self.relative_eta(rec);
+        let ggx = self.distribution(rec);
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        if ggx.is_smooth() {
+            // Plain glass, but with exact Fresnel
+            let r = microfacet::fresnel_dielectric(wo.z(), eta);
+            let normal = Vec3::new(0.0, 0.0, 1.0);
+            let wi = match microfacet::refract(wo, normal, eta) {
+                Some(wt) if random_f64() >= r => wt,
+                _ => microfacet::reflect(wo, normal),
+            };
+            return Some(ScatterRecord::specular(frame.transform(wi), white));
+        }
+
+        let wi = microfacet::dielectric_sample(&ggx, eta, wo)?;
+        let (f, pdf) = microfacet::dielectric_eval(&ggx, eta, wi, wo);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(ScatterRecord { direction: frame.transform(wi), throughput: white * (f / pdf), pdf, is_specular: false, wavelength: None })
+    }
+
+    fn eval(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> Color {
+        let ggx = self.distribution(rec);
+        if ggx.is_smooth() {
+            return Color::init_zero();
+        }
+        let frame = microfacet::shading_frame(rec.normal, rec.dpdu);
+        let (f, _) = microfacet::dielectric_eval(&ggx, self.relative_eta(rec), frame.to_local(wi), frame.to_local(wo));
+        Color::new(f, f, f)
+    }
+
+    fn pdf(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> f64 {
+        let ggx = self.distribution(rec);
+        if ggx.is_smooth() {
+            return 0.0;
+        }
+        let frame = microfacet::shading_frame(rec.normal, rec.dpdu);
+        microfacet::dielectric_eval(&ggx, self.relative_eta(rec), frame.to_local(wi), frame.to_local(wo)).1
+    }
+}
+
+impl Material for Principled {
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let frame = microfacet::shading_frame(rec.normal, rec.dpdu);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        let lobes = self.lobes(rec);
+
+        let wi = lobes.sample(wo)?;
+        let (f, pdf) = lobes.eval(wi, wo);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(ScatterRecord { direction: frame.transform(wi), throughput: f / pdf, pdf, is_specular: false, wavelength: None })
+    }
+
+    fn eval(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> Color {
+        let frame = microfacet::shading_frame(rec.normal, rec.dpdu);
+        self.lobes(rec).eval(frame.to_local(wi), frame.to_local(wo)).0
+    }
+
+    fn pdf(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> f64 {
+        let frame = microfacet::shading_frame(rec.normal, rec.dpdu);
+        self.lobes(rec).eval(frame.to_local(wi), frame.to_local(wo)).1
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.emission.value(u, v, p)
+    }
+}
+
+impl Material for Coated {
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        // Seen from inside the object there's no coat on this side
+        if !rec.front_face {
+            return self.base.sample(r_in, rec);
+        }
+
+        let frame = microfacet::shading_frame(rec.normal, rec.dpdu);
+        let wo = frame.to_local(-r_in.direction().unit_vector());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let ggx = self.distribution(rec);
+        let p_coat = self.coat_probability(wo);
+
+        let wi = if random_f64() < p_coat {
+            if ggx.is_smooth() {
+                let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+                let f = microfacet::fresnel_dielectric(wo.z(), self.refraction_index) / p_coat;
+                return Some(ScatterRecord::specular(frame.transform(wi), Color::new(f, f, f)));
+            }
+            let wi = microfacet::reflect(wo, ggx.sample_visible_normal(wo));
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            let srec = self.base.sample(r_in, rec)?;
+            let wi = frame.to_local(srec.direction.unit_vector());
+            if srec.is_specular {
+                let throughput = srec.throughput * self.attenuation(wi, wo) / (1.0 - p_coat);
+                return Some(ScatterRecord { throughput, ..srec });
+            }
+            wi
+        };
+
+        // Either lobe could have produced wi, so weigh it against both
+        let direction = frame.transform(wi);
+        let wo_world = -r_in.direction().unit_vector();
+        let (coat_f, coat_pdf) = self.coat_eval(&ggx, wi, wo);
+        let f = self.base.eval(rec, direction, wo_world) * self.attenuation(wi, wo) + Color::new(coat_f, coat_f, coat_f);
+        let pdf = p_coat * coat_pdf + (1.0 - p_coat) * self.base.pdf(rec, direction, wo_world);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(ScatterRecord { direction, throughput: f / pdf, pdf, is_specular: false, wavelength: None })
+    }
+
+    fn eval(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> Color {
+        if !rec.front_face {
+            return self.base.eval(rec, wi, wo);
+        }
+        let frame = microfacet::shading_frame(rec.normal, rec.dpdu);
+        let (wi_local, wo_local) = (frame.to_local(wi), frame.to_local(wo));
+        if wo_local.z() <= 0.0 {
+            return Color::init_zero();
+        }
+        let (coat_f, _) = self.coat_eval(&self.distribution(rec), wi_local, wo_local);
+        self.base.eval(rec, wi, wo) * self.attenuation(wi_local, wo_local) + Color::new(coat_f, coat_f, coat_f)
+    }
+
+    fn pdf(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> f64 {
+        if !rec.front_face {
+            return self.base.pdf(rec, wi, wo);
+        }
+        let frame = microfacet::shading_frame(rec.normal, rec.dpdu);
+        let (wi_local, wo_local) = (frame.to_local(wi), frame.to_local(wo));
+        if wo_local.z() <= 0.0 {
+            return 0.0;
+        }
+        let p_coat = self.coat_probability(wo_local);
+        let (_, coat_pdf) = self.coat_eval(&self.distribution(rec), wi_local, wo_local);
+        p_coat * coat_pdf + (1.0 - p_coat) * self.base.pdf(rec, wi, wo)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.base.emitted(u, v, p)
+    }
+}
+
+impl Material for MixMaterial {
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let w = self.weight(rec.u, rec.v, &rec.p);
+        if w <= 0.0 {
+            return self.a.sample(r_in, rec);
+        }
+        if w >= 1.0 {
+            return self.b.sample(r_in, rec);
+        }
+
+        let srec = if random_f64() < w { self.b.sample(r_in, rec)? } else { self.a.sample(r_in, rec)? };
+        // A specular lobe belongs to one material only; its selection probability cancels
+        if srec.is_specular {
+            return Some(srec);
+        }
+
+        // Either material could have scattered this way, so weigh the direction against both
+        let wo = -r_in.direction().unit_vector();
+        let f = self.eval(rec, srec.direction, wo);
+        let pdf = self.pdf(rec, srec.direction, wo);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(ScatterRecord { throughput: f / pdf, pdf, ..srec })
+    }
+
+    fn eval(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> Color {
+        let w = self.weight(rec.u, rec.v, &rec.p);
+        self.a.eval(rec, wi, wo) * (1.0 - w) + self.b.eval(rec, wi, wo) * w
+    }
+
+    fn pdf(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> f64 {
+        let w = self.weight(rec.u, rec.v, &rec.p);
+        self.a.pdf(rec, wi, wo) * (1.0 - w) + self.b.pdf(rec, wi, wo) * w
+    }
+
+    fn is_specular(&self) -> bool {
+        self.a.is_specular() && self.b.is_specular()
+    }
+
+    fn is_volume(&self) -> bool {
+        self.a.is_volume() && self.b.is_volume()
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let w = self.weight(u, v, p);
+        self.a.emitted(u, v, p) * (1.0 - w) + self.b.emitted(u, v, p) * w
     }
 }
 
@@ -140,9 +1001,7 @@ impl DiffuseLight {
 }
 
 impl Material for DiffuseLight {
-    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _attenuation: &mut Color, _scattered: &mut Ray) -> bool {
-        false
-    }
+    // Lights don't scatter: sample() keeps the default None
 
     fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
         self.emit.value(u, v, p)
@@ -167,9 +1026,180 @@ impl Isotropic {
 }
 
 impl Material for Isotropic {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
-        *scattered = Ray::new_time(rec.p, Vec3::random_unit_vector(), r_in.time());
-        *attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
-        return true;
+    // Uniform over the sphere, so the phase function and its pdf are both 1/(4 pi)
+    fn sample(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let throughput = self.albedo.value(rec.u, rec.v, &rec.p);
+        Some(ScatterRecord { direction: Vec3::random_unit_vector(), throughput, pdf: 1.0 / (4.0 * PI), is_specular: false, wavelength: None })
+    }
+
+    fn eval(&self, rec: &HitRecord, _wi: Vec3, _wo: Vec3) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p) / (4.0 * PI)
+    }
+
+    fn pdf(&self, _rec: &HitRecord, _wi: Vec3, _wo: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn is_volume(&self) -> bool {
+        true
+    }
+}
+
+
+// Henyey-Greenstein phase function for participating media. g in (-1, 1) sets the asymmetry:
+// positive scatters forward (haze, clouds), negative back toward the light, zero is isotropic.
+pub struct HenyeyGreenstein {
+    albedo: Arc<dyn Texture>,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(c: Color, g: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(c)), g)
+    }
+
+    pub fn from_texture(t: Arc<dyn Texture>, g: f64) -> Self {
+        Self { albedo: t, g: g.clamp(-0.99, 0.99) }
+    }
+
+    // Density over the sphere for turning by an angle with this cosine
+    fn phase(&self, cos_theta: f64) -> f64 {
+        let g = self.g;
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denom * denom.max(1e-12).sqrt())
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    // The phase function is sampled exactly, so the throughput is just the albedo
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let forward = r_in.direction().unit_vector();
+        let g = self.g;
+
+        // Invert the cumulative distribution of the scattering angle
+        let xi = random_f64();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_f64();
+
+        let local = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        let direction = ONB::new(forward).transform(local);
+        let throughput = self.albedo.value(rec.u, rec.v, &rec.p);
+        Some(ScatterRecord { direction, throughput, pdf: self.phase(cos_theta), is_specular: false, wavelength: None })
+    }
+
+    // wo points back along the incoming ray, so the path turns by the angle between -wo and wi
+    fn eval(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> Color {
+        self.albedo.value(rec.u, rec.v, &rec.p) * self.phase(-wo.dot(wi))
+    }
+
+    fn pdf(&self, _rec: &HitRecord, wi: Vec3, wo: Vec3) -> f64 {
+        self.phase(-wo.dot(wi))
+    }
+
+    fn is_volume(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every material with a non-specular lobe
+    fn materials() -> Vec<(&'static str, Arc<dyn Material>)> {
+        let grey = Color::new(0.6, 0.5, 0.4);
+        let mut plastic = Principled::new(grey);
+        plastic.sheen = Principled::constant(0.5);
+        let mut metal = Principled::new(grey);
+        metal.metallic = Principled::constant(0.7);
+        metal.roughness = Principled::constant(0.6);
+        let mut glass = Principled::new(grey);
+        glass.transmission = Principled::constant(0.6);
+
+        vec![
+            ("Lambertian", Arc::new(Lambertian::new(grey))),
+            ("Conductor", Arc::new(Conductor::gold(0.5))),
+            ("Conductor anisotropic", Arc::new(Conductor::anisotropic(Color::new(0.2, 0.9, 1.1), Color::new(3.9, 2.4, 2.2), 0.3, 0.55))),
+            ("RoughDielectric", Arc::new(RoughDielectric::new(1.5, 0.5))),
+            ("Principled plastic", Arc::new(plastic)),
+            ("Principled metal", Arc::new(metal)),
+            ("Principled glass", Arc::new(glass)),
+            ("Coated", Arc::new(Coated::new(Arc::new(Lambertian::new(grey)), 1.5, 0.5).with_tint(grey, 0.1))),
+            ("MixMaterial", Arc::new(MixMaterial::new(Arc::new(Lambertian::new(grey)), Arc::new(Conductor::copper(0.6)), 0.4))),
+            ("OrenNayar", Arc::new(OrenNayar::new(grey, 0.5))),
+            ("Isotropic", Arc::new(Isotropic::new(grey))),
+            ("HenyeyGreenstein", Arc::new(HenyeyGreenstein::new(grey, 0.5))),
+        ]
+    }
+
+    // A front-facing hit on a tilted surface, so the local frames are exercised too
+    fn hit_record<'a>() -> HitRecord<'a> {
+        let mut rec = HitRecord::new();
+        rec.normal = Vec3::new(0.3, 0.5, 0.8).unit_vector();
+        rec.t = 1.0;
+        rec.u = 0.5;
+        rec.v = 0.5;
+        rec
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-6 * a.abs().max(b.abs()).max(1.0)
+    }
+
+    // sample(), eval() and pdf() must describe the same distribution: the throughput of a
+    // sampled direction is eval / pdf, and pdf() integrates to the chance that sample()
+    // returns a direction at all
+    #[test]
+    fn sample_eval_pdf_agree() {
+        let rec = hit_record();
+        // 30 degrees off the normal
+        let wo = ONB::new(rec.normal).transform(Vec3::new(0.5, 0.0, 0.75f64.sqrt()));
+        let r_in = Ray::new(Point3::new(0.0, 0.0, 0.0) + wo, -wo);
+
+        for (name, material) in materials() {
+            let tries = 20_000;
+            let mut sampled = 0;
+            for _ in 0..tries {
+                let Some(srec) = material.sample(&r_in, &rec) else {
+                    continue;
+                };
+                if srec.is_specular {
+                    continue;
+                }
+                sampled += 1;
+
+                let wi = srec.direction.unit_vector();
+                let (f, pdf) = (material.eval(&rec, wi, wo), material.pdf(&rec, wi, wo));
+                assert!(close(srec.pdf, pdf), "{}: sampled pdf {} != pdf() {}", name, srec.pdf, pdf);
+                let expected = f / pdf;
+                for axis in 0..3 {
+                    assert!(close(srec.throughput[axis], expected[axis]),
+                        "{}: throughput {:?} != eval / pdf {:?}", name, srec.throughput, expected);
+                }
+            }
+            let acceptance = sampled as f64 / tries as f64;
+
+            // Stratified over the whole sphere, which covers reflection and transmission
+            let (n_cos, n_phi) = (256, 512);
+            let mut integral = 0.0;
+            for i in 0..n_cos {
+                for j in 0..n_phi {
+                    let z = 1.0 - 2.0 * (i as f64 + random_f64()) / n_cos as f64;
+                    let phi = 2.0 * PI * (j as f64 + random_f64()) / n_phi as f64;
+                    let s = (1.0 - z * z).max(0.0).sqrt();
+                    integral += material.pdf(&rec, Vec3::new(s * phi.cos(), s * phi.sin(), z), wo);
+                }
+            }
+            integral *= 4.0 * PI / (n_cos * n_phi) as f64;
+
+            assert!(acceptance > 0.9, "{}: only {} of samples succeed", name, acceptance);
+            assert!((integral - acceptance).abs() < 0.01, "{}: pdf integrates to {}, sample() succeeds {} of the time", name, integral, acceptance);
+        }
     }
 }
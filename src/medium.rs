@@ -0,0 +1,292 @@
+// medium.rs
+// Participating media: smoke, fog, murky water, whatever fills the space between surfaces. A
+// medium has no shape of its own. Surfaces whose material is a MediumInterface bound it, and
+// the camera keeps a stack of the media each path is in, entering a medium through the front of
+// its boundary and leaving it through the back. So boundaries can be any closed shape, convex or
+// not, media can nest (smoke inside a glass sphere inside fog), and the camera can start inside
+// one.
+
+use std::sync::Arc;
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord, Isotropic, HenyeyGreenstein};
+use crate::ray::Ray;
+use crate::utils::prelude::random_f64;
+use crate::vec3::{Vec3, Color, Point3};
+
+// What a medium did to a ray over one stretch: stopped it to scatter, or let it through. Either
+// way the path throughput is multiplied by `weight`
+pub struct MediumSample {
+    pub scattered: bool,
+    pub weight: Color,
+}
+
+pub trait Medium: Send + Sync {
+    // Sample how far `r` gets from its origin before scattering, up to `t_max` where the next
+    // surface is (infinite if there is none). On a scatter `rec` gets the point and the phase
+    // function
+    fn sample<'a>(&'a self, r: &Ray, t_max: f64, rec: &mut HitRecord<'a>) -> MediumSample;
+
+    // Fraction of light that makes it from the origin of `r` to `t_max`, for shadow rays
+    fn transmittance(&self, r: &Ray, t_max: f64) -> Color;
+}
+
+// Fill in a scattering event at `t` along `r`
+pub(crate) fn scatter_at<'a>(r: &Ray, t: f64, phase_function: &'a dyn Material, rec: &mut HitRecord<'a>) {
+    rec.t = t;
+    rec.p = r.at(t);
+    rec.normal = Vec3::new(1.0, 0.0, 0.0);
+    rec.front_face = true;
+    rec.u = 0.0;
+    rec.v = 0.0;
+    rec.material = Some(phase_function);
+}
+
+// Beer-Lambert per channel; a channel that doesn't attenuate lets everything through, even
+// over an infinite distance
+fn beer_lambert(sigma_t: Color, distance: f64) -> Color {
+    let tr = |sigma: f64| if sigma <= 0.0 { 1.0 } else { (-sigma * distance).exp() };
+    Color::new(tr(sigma_t.x()), tr(sigma_t.y()), tr(sigma_t.z()))
+}
+
+// Material for a surface that bounds a medium: `inside` fills it, and `surface` scatters as usual
+// (glass around smoke, say). Without a surface the boundary is invisible and rays go straight on
+pub struct MediumInterface {
+    surface: Option<Arc<dyn Material>>,
+    inside: Arc<dyn Medium>,
+}
+
+impl MediumInterface {
+    pub fn new(surface: Arc<dyn Material>, inside: Arc<dyn Medium>) -> Self {
+        Self { surface: Some(surface), inside }
+    }
+
+    pub fn invisible(inside: Arc<dyn Medium>) -> Self {
+        Self { surface: None, inside }
+    }
+}
+
+impl Material for MediumInterface {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        self.surface.as_ref()?.sample(r_in, rec)
+    }
+
+    fn eval(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> Color {
+        self.surface.as_ref().map_or(Color::init_zero(), |s| s.eval(rec, wi, wo))
+    }
+
+    fn pdf(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> f64 {
+        self.surface.as_ref().map_or(0.0, |s| s.pdf(rec, wi, wo))
+    }
+
+    fn is_specular(&self) -> bool {
+        self.surface.as_ref().is_some_and(|s| s.is_specular())
+    }
+
+    fn is_volume(&self) -> bool {
+        self.surface.as_ref().is_some_and(|s| s.is_volume())
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.surface.as_ref().map_or(Color::init_zero(), |s| s.emitted(u, v, p))
+    }
+
+    fn medium(&self) -> Option<&dyn Medium> {
+        Some(self.inside.as_ref())
+    }
+
+    fn is_invisible(&self) -> bool {
+        self.surface.is_none()
+    }
+}
+
+// Gray homogeneous medium: free flights are exponential in the density, and the phase
+// function's albedo says how much of the light survives each collision
+pub struct HomogeneousMedium {
+    density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl HomogeneousMedium {
+    pub fn new(density: f64, phase_function: Arc<dyn Material>) -> Self {
+        Self { density, phase_function }
+    }
+
+    pub fn from_color(density: f64, albedo: &Color) -> Self {
+        Self::new(density, Arc::new(Isotropic::new(*albedo)))
+    }
+}
+
+impl Medium for HomogeneousMedium {
+    fn sample<'a>(&'a self, r: &Ray, t_max: f64, rec: &mut HitRecord<'a>) -> MediumSample {
+        let weight = Color::new(1.0, 1.0, 1.0);
+        if self.density <= 0.0 {
+            return MediumSample { scattered: false, weight };
+        }
+
+        let ray_length = r.direction().length();
+        let hit_distance = -(1.0 - random_f64()).ln() / self.density;
+        if hit_distance >= t_max * ray_length {
+            return MediumSample { scattered: false, weight };
+        }
+
+        scatter_at(r, hit_distance / ray_length, self.phase_function.as_ref(), rec);
+        MediumSample { scattered: true, weight }
+    }
+
+    fn transmittance(&self, r: &Ray, t_max: f64) -> Color {
+        let density = self.density.max(0.0);
+        beer_lambert(Color::new(density, density, density), t_max * r.direction().length())
+    }
+}
+
+// Medium that only absorbs, per RGB channel: the inside of tinted glass. Nothing scatters, so
+// every stretch a path travels through it is simply dimmed by Beer-Lambert
+pub struct AbsorbingMedium {
+    sigma_a: Color,
+}
+
+impl AbsorbingMedium {
+    pub fn new(sigma_a: Color) -> Self {
+        Self { sigma_a }
+    }
+}
+
+impl Medium for AbsorbingMedium {
+    fn sample<'a>(&'a self, r: &Ray, t_max: f64, _rec: &mut HitRecord<'a>) -> MediumSample {
+        MediumSample { scattered: false, weight: self.transmittance(r, t_max) }
+    }
+
+    fn transmittance(&self, r: &Ray, t_max: f64) -> Color {
+        beer_lambert(self.sigma_a, t_max * r.direction().length())
+    }
+}
+
+// Homogeneous medium whose absorption and scattering differ per RGB channel (murky water, skin),
+// optionally glowing. Each free flight is sampled in one channel picked at random, and weighted
+// against the average of all three channels' densities (one-sample MIS), so no channel's weight
+// blows up however far apart their coefficients are.
+pub struct ChromaticMedium {
+    sigma_t: Color,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ChromaticMedium {
+    pub fn new(sigma_a: Color, sigma_s: Color, g: f64) -> Self {
+        // The phase function's albedo is sigma_s itself: the flight weight below is
+        // transmittance over the density of stopping there, which leaves sigma_s to apply
+        Self { sigma_t: sigma_a + sigma_s, phase_function: Arc::new(HenyeyGreenstein::new(sigma_s, g)) }
+    }
+
+    // Radiance emitted per unit length, for fire and glowing gas
+    pub fn with_emission(mut self, emission: Color) -> Self {
+        self.phase_function = Arc::new(EmissivePhase::new(self.phase_function, emission));
+        self
+    }
+}
+
+impl Medium for ChromaticMedium {
+    fn sample<'a>(&'a self, r: &Ray, t_max: f64, rec: &mut HitRecord<'a>) -> MediumSample {
+        // A channel that doesn't attenuate never collides, which the MIS weights account for
+        let channel = ((random_f64() * 3.0) as usize).min(2);
+        let sigma = self.sigma_t[channel];
+        let ray_length = r.direction().length();
+        let distance = t_max * ray_length;
+        let hit_distance = if sigma > 0.0 { -(1.0 - random_f64()).ln() / sigma } else { f64::INFINITY };
+
+        if hit_distance < distance {
+            let tr = beer_lambert(self.sigma_t, hit_distance);
+            let stop = (self.sigma_t.x() * tr.x() + self.sigma_t.y() * tr.y() + self.sigma_t.z() * tr.z()) / 3.0;
+            if stop <= 0.0 {
+                return MediumSample { scattered: false, weight: Color::init_zero() };
+            }
+            scatter_at(r, hit_distance / ray_length, self.phase_function.as_ref(), rec);
+            return MediumSample { scattered: true, weight: tr / stop };
+        }
+
+        // Getting through had the average of the channels' chances
+        let tr = beer_lambert(self.sigma_t, distance);
+        let pass = (tr.x() + tr.y() + tr.z()) / 3.0;
+        let weight = if pass <= 0.0 { Color::init_zero() } else { tr / pass };
+        MediumSample { scattered: false, weight }
+    }
+
+    fn transmittance(&self, r: &Ray, t_max: f64) -> Color {
+        beer_lambert(self.sigma_t, t_max * r.direction().length())
+    }
+}
+
+// A medium's phase function, also giving off light at each collision
+pub(crate) struct EmissivePhase {
+    phase: Arc<dyn Material>,
+    emission: Color,
+}
+
+impl EmissivePhase {
+    pub(crate) fn new(phase: Arc<dyn Material>, emission: Color) -> Self {
+        Self { phase, emission }
+    }
+}
+
+impl Material for EmissivePhase {
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        self.phase.sample(r_in, rec)
+    }
+
+    fn eval(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> Color {
+        self.phase.eval(rec, wi, wo)
+    }
+
+    fn pdf(&self, rec: &HitRecord, wi: Vec3, wo: Vec3) -> f64 {
+        self.phase.pdf(rec, wi, wo)
+    }
+
+    fn is_volume(&self) -> bool {
+        true
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.emission
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Chromatic free flights across a slab of length `distance`, per channel: the mean weight of
+    // the paths that get through, and of those that collide times that channel's sigma_t
+    fn flights(medium: &ChromaticMedium, distance: f64, samples: usize) -> (Color, Color) {
+        // A direction of length 2, so t and distance differ
+        let r = Ray::new(Point3::init_zero(), Vec3::new(0.0, 0.0, 2.0));
+        let (mut passed, mut collided) = (Color::init_zero(), Color::init_zero());
+        for _ in 0..samples {
+            let mut rec = HitRecord::new();
+            let event = medium.sample(&r, distance / 2.0, &mut rec);
+            if event.scattered {
+                assert!(rec.t < distance / 2.0);
+                collided = collided + event.weight * medium.sigma_t;
+            } else {
+                passed = passed + event.weight;
+            }
+        }
+        (passed / samples as f64, collided / samples as f64)
+    }
+
+    #[test]
+    fn chromatic_flights_follow_beer_lambert_per_channel() {
+        // Coefficients an order of magnitude apart, and a channel that never collides
+        let medium = ChromaticMedium::new(Color::new(0.1, 1.0, 0.0), Color::new(0.2, 1.5, 0.0), 0.0);
+        let distance = 1.0;
+        let (passed, collided) = flights(&medium, distance, 1_000_000);
+
+        // Getting through is worth the transmittance, and colliding anywhere along the way
+        // the rest of it: the integral of sigma_t * exp(-sigma_t s) over the slab
+        let expected = beer_lambert(medium.sigma_t, distance);
+        for c in 0..3 {
+            assert!((passed[c] - expected[c]).abs() < 0.01, "channel {}: passed {} != {}", c, passed[c], expected[c]);
+            let absorbed = 1.0 - expected[c];
+            assert!((collided[c] - absorbed).abs() < 0.01, "channel {}: collided {} != {}", c, collided[c], absorbed);
+        }
+        assert_eq!(medium.transmittance(&Ray::new(Point3::init_zero(), Vec3::new(0.0, 0.0, 2.0)), distance / 2.0), expected);
+    }
+}
@@ -0,0 +1,266 @@
+// microfacet.rs
+// GGX / Trowbridge-Reitz microfacet distribution and Fresnel terms shared by the rough materials.
+//
+// Everything works in the local shading frame, where the surface normal is +z. alpha_x and
+// alpha_y are the roughness along the frame's u and v axes (equal for isotropic surfaces).
+
+use crate::onb::ONB;
+use crate::utils::prelude::{random_f64, PI};
+use crate::vec3::{Color, Vec3};
+
+// Below this alpha a surface is treated as perfectly smooth: the distribution is too peaked
+// to evaluate, and sampling it is a mirror reflection anyway
+pub const MIN_ALPHA: f64 = 1e-3;
+
+// Perceptual roughness in [0, 1] to GGX alpha
+#[inline]
+pub fn roughness_to_alpha(roughness: f64) -> f64 {
+    let r = roughness.clamp(0.0, 1.0);
+    r * r
+}
+
+// Shading frame around the normal, with the tangent along `dpdu` (the direction of increasing
+// texture u that the shape recorded in the hit), so brushed-metal anisotropy follows the
+// surface parametrization. Without it, or where it vanishes (a sphere's poles), the tangent
+// follows the horizontal circles around the world y axis, and the x axis on horizontal surfaces.
+pub fn shading_frame(normal: Vec3, dpdu: Option<Vec3>) -> ONB {
+    let along_surface = |t: Vec3| t - normal * normal.dot(t);
+    if let Some(t) = dpdu.filter(|t| t.length_squared() > 0.0).map(|t| along_surface(t.unit_vector()))
+        && t.length_squared() > 1e-8
+    {
+        return ONB::from_normal_tangent(normal, t);
+    }
+
+    let tangent = Vec3::new(0.0, 1.0, 0.0).cross(normal);
+    if tangent.length_squared() < 1e-8 {
+        return ONB::from_normal_tangent(normal, Vec3::new(1.0, 0.0, 0.0));
+    }
+    ONB::from_normal_tangent(normal, tangent)
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct GGX {
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+impl GGX {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        Self { alpha_x: alpha_x.max(MIN_ALPHA), alpha_y: alpha_y.max(MIN_ALPHA) }
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x <= MIN_ALPHA && self.alpha_y <= MIN_ALPHA
+    }
+
+    // Distribution of microfacet normals h (h.z > 0)
+    pub fn d(&self, h: Vec3) -> f64 {
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+        let x = h.x() / self.alpha_x;
+        let y = h.y() / self.alpha_y;
+        let z = h.z();
+        let t = x * x + y * y + z * z;
+        1.0 / (PI * self.alpha_x * self.alpha_y * t * t)
+    }
+
+    // Smith auxiliary function
+    pub fn lambda(&self, w: Vec3) -> f64 {
+        let z2 = w.z() * w.z();
+        if z2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let a2 = (self.alpha_x * w.x()).powi(2) + (self.alpha_y * w.y()).powi(2);
+        (-1.0 + (1.0 + a2 / z2).sqrt()) / 2.0
+    }
+
+    // Masking for one direction
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Height-correlated masking-shadowing
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of visible normals seen from wo (wo.z > 0)
+    pub fn visible_d(&self, wo: Vec3, h: Vec3) -> f64 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(h).max(0.0) * self.d(h) / wo.z()
+    }
+
+    // Sample a microfacet normal from the distribution of normals visible from wo
+    // (Heitz 2018, "Sampling the GGX Distribution of Visible Normals")
+    pub fn sample_visible_normal(&self, wo: Vec3) -> Vec3 {
+        // Stretch to the hemisphere configuration
+        let vh = Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).unit_vector();
+
+        // Orthonormal basis around vh
+        let lensq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if lensq > 0.0 { Vec3::new(-vh.y(), vh.x(), 0.0) / lensq.sqrt() } else { Vec3::new(1.0, 0.0, 0.0) };
+        let t2 = vh.cross(t1);
+
+        // Uniform point on the projected disk, squashed toward the visible half
+        let r = random_f64().sqrt();
+        let phi = 2.0 * PI * random_f64();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        // Back onto the hemisphere, then unstretch
+        let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+        Vec3::new(self.alpha_x * nh.x(), self.alpha_y * nh.y(), nh.z().max(1e-6)).unit_vector()
+    }
+}
+
+// Mirror wo about the microfacet normal h
+#[inline]
+pub fn reflect(wo: Vec3, h: Vec3) -> Vec3 {
+    h * (2.0 * wo.dot(h)) - wo
+}
+
+// Unpolarized Fresnel reflectance of a conductor with complex index of refraction eta + i k,
+// per color channel
+pub fn fresnel_conductor(cos_theta_i: f64, eta: Color, k: Color) -> Color {
+    Color::new(
+        fresnel_conductor_channel(cos_theta_i, eta.x(), k.x()),
+        fresnel_conductor_channel(cos_theta_i, eta.y(), k.y()),
+        fresnel_conductor_channel(cos_theta_i, eta.z(), k.z()),
+    )
+}
+
+fn fresnel_conductor_channel(cos_theta_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta_i.clamp(0.0, 1.0) * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+// Refract wo through a microfacet with normal h, where eta is the index on the far side over
+// the index on wo's side. None on total internal reflection.
+pub fn refract(wo: Vec3, h: Vec3, eta: f64) -> Option<Vec3> {
+    let (mut h, mut eta) = (h, eta);
+    let mut cos_i = wo.dot(h);
+    if cos_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_i = -cos_i;
+        h = -h;
+    }
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-wo / eta + h * (cos_i / eta - cos_t))
+}
+
+// Exact unpolarized Fresnel reflectance of a dielectric interface. eta is the index on the far
+// side over the index on the incident side; a negative cosine means the ray comes from the
+// far side.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let (mut cos_i, mut eta) = (cos_theta_i.clamp(-1.0, 1.0), eta);
+    if cos_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_i = -cos_i;
+    }
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (r_parallel * r_parallel + r_perpendicular * r_perpendicular)
+}
+
+// Rough dielectric interface in the local frame, with wo.z > 0 and eta the index below the
+// surface over the one above: bsdf * |cos(wi)| and the density of dielectric_sample picking wi.
+// Transmission isn't scaled by 1/eta^2, so closed objects come out neutral.
+pub fn dielectric_eval(ggx: &GGX, eta: f64, wi: Vec3, wo: Vec3) -> (f64, f64) {
+    if wo.z() <= 0.0 || wi.z() == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    // Generalized half vector, on the side of the normal
+    let reflect = wi.z() > 0.0;
+    let etap = if reflect { 1.0 } else { eta };
+    let h = wi * etap + wo;
+    if h.length_squared() == 0.0 {
+        return (0.0, 0.0);
+    }
+    let h = h.unit_vector();
+    let h = if h.z() < 0.0 { -h } else { h };
+
+    // Microfacets seen from behind don't contribute
+    if h.dot(wi) * wi.z() < 0.0 || h.dot(wo) <= 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let r = fresnel_dielectric(wo.dot(h), eta);
+    let t = 1.0 - r;
+
+    if reflect {
+        let f = ggx.d(h) * ggx.g(wo, wi) * r / (4.0 * wo.z());
+        let pdf = ggx.visible_d(wo, h) / (4.0 * wo.dot(h)) * r;
+        (f, pdf)
+    } else {
+        let denom = (wi.dot(h) + wo.dot(h) / etap).powi(2);
+        let f = ggx.d(h) * ggx.g(wo, wi) * t * (wi.dot(h) * wo.dot(h) / (denom * wo.z())).abs();
+        let pdf = ggx.visible_d(wo, h) * wi.dot(h).abs() / denom * t;
+        (f, pdf)
+    }
+}
+
+// Reflect or refract wo off a visible microfacet, picking by the microfacet's Fresnel weight.
+// None if the sampled direction ends up on the wrong side.
+pub fn dielectric_sample(ggx: &GGX, eta: f64, wo: Vec3) -> Option<Vec3> {
+    let h = ggx.sample_visible_normal(wo);
+    let r = fresnel_dielectric(wo.dot(h), eta);
+    match refract(wo, h, eta) {
+        Some(wt) if random_f64() >= r => (wt.z() < 0.0).then_some(wt),
+        _ => {
+            let wr = reflect(wo, h);
+            (wr.z() > 0.0).then_some(wr)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shading_frame_follows_dpdu() {
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+
+        // The tangent is dpdu moved into the surface plane
+        let frame = shading_frame(normal, Some(Vec3::new(0.0, 3.0, 1.0)));
+        assert!((frame.u() - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-12);
+        assert!((frame.w() - normal).length() < 1e-12);
+
+        // Missing, zero or parallel to the normal: around the y axis, or along x here
+        for dpdu in [None, Some(Vec3::init_zero()), Some(Vec3::new(0.0, 0.0, 2.0))] {
+            let frame = shading_frame(normal, dpdu);
+            assert!((frame.u() - Vec3::new(1.0, 0.0, 0.0)).length() < 1e-12, "{:?}", dpdu);
+        }
+        let wall = shading_frame(Vec3::new(1.0, 0.0, 0.0), None);
+        assert!((wall.u() - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-12);
+    }
+}
@@ -0,0 +1,681 @@
+// obj_loader.rs
+// Wavefront OBJ + MTL importer.
+//
+// Reads positions, texture coordinates, normals and polygon faces (fan-triangulated), split into
+// one TriangleMesh per group/material pair. Materials from `mtllib` files are mapped onto the
+// existing material types:
+//   PBR extension (Pr, Pm, Ps, Pc, map_Pr, map_Pm) -> Principled, with Kd/map_Kd as the base
+//                                           color, Ke/map_Ke as emission and 1 - d as
+//                                           transmission
+//   Ke / map_Ke                          -> DiffuseLight
+//   d < 1 (or Tr > 0), illum 4, 6, 7, 9  -> Dielectric with Ni as the refraction index, tinted
+//                                           by Tf as the transmittance over
+//                                           ObjOptions::transmittance_distance
+//   illum 3                              -> Metal with Ks as albedo, fuzz from Ns
+//   anything else                        -> Lambertian with Kd, or an ImageTexture for map_Kd
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::hittable_list::HittableList;
+use crate::material::{Material, Lambertian, Metal, Dielectric, Principled, DiffuseLight};
+use crate::texture::{Texture, SolidColor, ImageTexture, TextureFilter, WrapMode};
+use crate::triangle::TriangleMesh;
+use crate::vec3::{Color, Point3, Vec3};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { path: PathBuf, source: io::Error },
+    Parse { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "cannot read {}: {}", path.display(), source),
+            ObjError::Parse { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+pub struct ObjOptions {
+    // Used for faces with no `usemtl`
+    pub default_material: Arc<dyn Material>,
+    // When set, every face uses this material and the MTL files are not read
+    pub material_override: Option<Arc<dyn Material>>,
+    // Glass keeps its MTL `Tf` color after light travels this far through it, in model units.
+    // Scale it with the model so the tint does not depend on the units it was exported in.
+    pub transmittance_distance: f64,
+}
+
+impl ObjOptions {
+    pub fn new() -> Self {
+        Self {
+            default_material: Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73))),
+            material_override: None,
+            transmittance_distance: 1.0,
+        }
+    }
+}
+
+impl Default for ObjOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Load an OBJ file with its materials. Each group/material pair becomes one TriangleMesh
+// (with its own BVH) in the returned list.
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<HittableList, ObjError> {
+    load_obj_with(path, &ObjOptions::new())
+}
+
+pub fn load_obj_with<P: AsRef<Path>>(path: P, options: &ObjOptions) -> Result<HittableList, ObjError> {
+    let path = path.as_ref();
+    let source = read_file(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+
+    let mut parser = ObjParser::new(path, base_dir, options);
+    for (i, line) in source.lines().enumerate() {
+        parser.line(i + 1, line)?;
+    }
+    parser.finish()
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io { path: path.to_path_buf(), source })
+}
+
+// Vertex attributes as referenced by a face corner (0-based, already resolved)
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+// Triangles collected for one group/material pair
+struct MeshBuilder {
+    line: usize, // of the first face, where mesh errors are reported
+    material: Arc<dyn Material>,
+    vertex_ids: HashMap<Corner, u32>,
+    positions: Vec<Point3>,
+    normals: Vec<Option<Vec3>>,
+    uvs: Vec<Option<(f64, f64)>>,
+    indices: Vec<[u32; 3]>,
+}
+
+impl MeshBuilder {
+    fn new(line: usize, material: Arc<dyn Material>) -> Self {
+        Self {
+            line,
+            material,
+            vertex_ids: HashMap::new(),
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    // Each distinct (position, uv, normal) corner becomes one mesh vertex
+    fn vertex(&mut self, corner: Corner, positions: &[Point3], uvs: &[(f64, f64)], normals: &[Vec3]) -> u32 {
+        if let Some(&id) = self.vertex_ids.get(&corner) {
+            return id;
+        }
+        let id = self.positions.len() as u32;
+        self.positions.push(positions[corner.position]);
+        self.uvs.push(corner.uv.map(|i| uvs[i]));
+        self.normals.push(corner.normal.map(|i| normals[i]));
+        self.vertex_ids.insert(corner, id);
+        id
+    }
+
+    fn build(self) -> Result<TriangleMesh, String> {
+        // Only keep normals/uvs if the file gave some; fill holes with the face normal / (0, 0)
+        let normals = if self.normals.iter().any(|n| n.is_some()) {
+            let mut face_normals = vec![Vec3::init_zero(); self.positions.len()];
+            for tri in &self.indices {
+                let [a, b, c] = tri.map(|i| self.positions[i as usize]);
+                let n = (b - a).cross(c - a);
+                for &i in tri {
+                    face_normals[i as usize] = face_normals[i as usize] + n;
+                }
+            }
+            Some(self.normals.iter().zip(face_normals).map(|(n, f)| n.unwrap_or(f)).collect())
+        } else {
+            None
+        };
+        let uvs = if self.uvs.iter().any(|t| t.is_some()) {
+            Some(self.uvs.iter().map(|t| t.unwrap_or((0.0, 0.0))).collect())
+        } else {
+            None
+        };
+        TriangleMesh::new(self.positions, normals, uvs, self.indices, self.material)
+    }
+}
+
+struct ObjParser<'o> {
+    path: &'o Path,
+    base_dir: &'o Path,
+    options: &'o ObjOptions,
+
+    positions: Vec<Point3>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<Vec3>,
+
+    materials: HashMap<String, Arc<dyn Material>>,
+    group: String,
+    material_name: Option<String>,
+
+    // (group, material) -> mesh, in order of first use
+    mesh_ids: HashMap<(String, Option<String>), usize>,
+    meshes: Vec<MeshBuilder>,
+}
+
+impl<'o> ObjParser<'o> {
+    fn new(path: &'o Path, base_dir: &'o Path, options: &'o ObjOptions) -> Self {
+        Self {
+            path,
+            base_dir,
+            options,
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            materials: HashMap::new(),
+            group: String::from("default"),
+            material_name: None,
+            mesh_ids: HashMap::new(),
+            meshes: Vec::new(),
+        }
+    }
+
+    fn error(&self, line: usize, message: String) -> ObjError {
+        ObjError::Parse { path: self.path.to_path_buf(), line, message }
+    }
+
+    fn line(&mut self, line_no: usize, line: &str) -> Result<(), ObjError> {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            return Ok(());
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                // `v x y z [w]`, or `v x y z r g b` with vertex colors, which are ignored
+                let v = parse_floats(&args, 3, 7).map_err(|m| self.error(line_no, format!("bad vertex: {}", m)))?;
+                self.positions.push(Point3::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let t = parse_floats(&args, 1, 3).map_err(|m| self.error(line_no, format!("bad texture coordinate: {}", m)))?;
+                self.uvs.push((t[0], t.get(1).copied().unwrap_or(0.0)));
+            }
+            "vn" => {
+                let n = parse_floats(&args, 3, 3).map_err(|m| self.error(line_no, format!("bad normal: {}", m)))?;
+                self.normals.push(Vec3::new(n[0], n[1], n[2]));
+            }
+            "f" => self.face(line_no, &args)?,
+            "g" | "o" => {
+                self.group = if args.is_empty() { String::from("default") } else { args.join(" ") };
+            }
+            "usemtl" => {
+                let Some(name) = args.first() else {
+                    return Err(self.error(line_no, "usemtl without a material name".to_string()));
+                };
+                if self.options.material_override.is_none() && !self.materials.contains_key(*name) {
+                    return Err(self.error(line_no, format!("usemtl refers to undefined material `{}`", name)));
+                }
+                self.material_name = Some(name.to_string());
+            }
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(self.error(line_no, "mtllib without a file name".to_string()));
+                }
+                if self.options.material_override.is_none() {
+                    // Any number of files, later ones overriding materials of the same name
+                    for file in &args {
+                        let materials = load_mtl(&self.base_dir.join(file), self.options)?;
+                        self.materials.extend(materials);
+                    }
+                }
+            }
+            // Smoothing groups, lines, points, free-form geometry and the rarer statements are not used
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn face(&mut self, line_no: usize, args: &[&str]) -> Result<(), ObjError> {
+        if args.len() < 3 {
+            return Err(self.error(line_no, format!("face needs at least 3 vertices, got {}", args.len())));
+        }
+
+        let mut corners = Vec::with_capacity(args.len());
+        for arg in args {
+            corners.push(self.corner(line_no, arg)?);
+        }
+
+        let key = (self.group.clone(), self.material_name.clone());
+        let mesh_id = match self.mesh_ids.get(&key) {
+            Some(&id) => id,
+            None => {
+                let material = self.current_material();
+                self.meshes.push(MeshBuilder::new(line_no, material));
+                self.mesh_ids.insert(key, self.meshes.len() - 1);
+                self.meshes.len() - 1
+            }
+        };
+
+        let mesh = &mut self.meshes[mesh_id];
+        let ids: Vec<u32> = corners
+            .iter()
+            .map(|c| mesh.vertex(*c, &self.positions, &self.uvs, &self.normals))
+            .collect();
+
+        // Fan triangulation, fine for the convex polygons OBJ exporters write
+        for i in 1..ids.len() - 1 {
+            mesh.indices.push([ids[0], ids[i], ids[i + 1]]);
+        }
+        Ok(())
+    }
+
+    // Parse `v`, `v/vt`, `v//vn` or `v/vt/vn`; negative indices count back from the end
+    fn corner(&self, line_no: usize, arg: &str) -> Result<Corner, ObjError> {
+        let parts: Vec<&str> = arg.split('/').collect();
+        if parts.len() > 3 || parts[0].is_empty() {
+            return Err(self.error(line_no, format!("bad face vertex `{}`", arg)));
+        }
+        let optional = |i: usize| parts.get(i).copied().filter(|p| !p.is_empty());
+
+        let position = self.index(line_no, parts[0], self.positions.len(), "vertex")?;
+        let uv = match optional(1) {
+            Some(part) => Some(self.index(line_no, part, self.uvs.len(), "texture coordinate")?),
+            None => None,
+        };
+        let normal = match optional(2) {
+            Some(part) => Some(self.index(line_no, part, self.normals.len(), "normal")?),
+            None => None,
+        };
+        Ok(Corner { position, uv, normal })
+    }
+
+    fn index(&self, line_no: usize, part: &str, count: usize, what: &str) -> Result<usize, ObjError> {
+        let Ok(i) = part.parse::<i64>() else {
+            return Err(self.error(line_no, format!("bad {} index `{}`", what, part)));
+        };
+        let resolved = if i > 0 { i - 1 } else { count as i64 + i };
+        if i == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(self.error(line_no, format!("{} index {} out of range (have {})", what, i, count)));
+        }
+        Ok(resolved as usize)
+    }
+
+    fn current_material(&self) -> Arc<dyn Material> {
+        if let Some(m) = &self.options.material_override {
+            return m.clone();
+        }
+        match &self.material_name {
+            Some(name) => self.materials[name].clone(),
+            None => self.options.default_material.clone(),
+        }
+    }
+
+    fn finish(mut self) -> Result<HittableList, ObjError> {
+        let mut list = HittableList::new();
+        for mesh in std::mem::take(&mut self.meshes) {
+            if !mesh.indices.is_empty() {
+                let line = mesh.line;
+                list.add(Box::new(mesh.build().map_err(|message| self.error(line, message))?));
+            }
+        }
+        Ok(list)
+    }
+}
+
+fn parse_floats(args: &[&str], min: usize, max: usize) -> Result<Vec<f64>, String> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max { format!("{}", min) } else { format!("{} to {}", min, max) };
+        return Err(format!("expected {} numbers, got {}", expected, args.len()));
+    }
+    args.iter()
+        .map(|a| a.parse::<f64>().map_err(|_| format!("`{}` is not a number", a)))
+        .collect()
+}
+
+
+// MTL parsing
+
+// The subset of MTL statements we map onto materials
+struct MtlDef {
+    line: usize,
+    kd: Color,
+    ks: Color,
+    ke: Color,
+    tf: Color,
+    ns: f64,
+    ni: f64,
+    d: f64,
+    illum: u32,
+    map_kd: Option<PathBuf>,
+    map_ke: Option<PathBuf>,
+    // PBR extension
+    pr: Option<f64>,
+    pm: Option<f64>,
+    ps: Option<f64>,
+    pc: Option<f64>,
+    map_pr: Option<PathBuf>,
+    map_pm: Option<PathBuf>,
+}
+
+impl MtlDef {
+    fn new(line: usize) -> Self {
+        Self {
+            line,
+            kd: Color::new(0.8, 0.8, 0.8),
+            ks: Color::init_zero(),
+            ke: Color::init_zero(),
+            tf: Color::new(1.0, 1.0, 1.0),
+            ns: 0.0,
+            ni: 1.5,
+            d: 1.0,
+            illum: 2,
+            map_kd: None,
+            map_ke: None,
+            pr: None,
+            pm: None,
+            ps: None,
+            pc: None,
+            map_pr: None,
+            map_pm: None,
+        }
+    }
+}
+
+fn load_mtl(path: &Path, options: &ObjOptions) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let source = read_file(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let error = |line: usize, message: String| ObjError::Parse { path: path.to_path_buf(), line, message };
+
+    let mut defs: Vec<(String, MtlDef)> = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(error(line_no, "newmtl without a name".to_string()));
+            }
+            defs.push((args.join(" "), MtlDef::new(line_no)));
+            continue;
+        }
+
+        let Some((_, def)) = defs.last_mut() else {
+            return Err(error(line_no, format!("`{}` before the first newmtl", keyword)));
+        };
+
+        let color = |args: &[&str]| -> Result<Color, ObjError> {
+            // A single value means grey
+            let c = parse_floats(args, 1, 3).map_err(|m| error(line_no, format!("bad {} color: {}", keyword, m)))?;
+            Ok(match c.len() {
+                1 => Color::new(c[0], c[0], c[0]),
+                3 => Color::new(c[0], c[1], c[2]),
+                _ => return Err(error(line_no, format!("bad {} color: expected 1 or 3 numbers", keyword))),
+            })
+        };
+        let scalar = |args: &[&str]| -> Result<f64, ObjError> {
+            let v = parse_floats(args, 1, 1).map_err(|m| error(line_no, format!("bad {} value: {}", keyword, m)))?;
+            Ok(v[0])
+        };
+        let texture_path = |args: &[&str]| -> Result<PathBuf, ObjError> {
+            match texture_file(args) {
+                Some(file) => Ok(base_dir.join(file)),
+                None => Err(error(line_no, format!("{} without a file name", keyword))),
+            }
+        };
+
+        match keyword {
+            "Kd" => def.kd = color(&args)?,
+            "Ks" => def.ks = color(&args)?,
+            "Ke" => def.ke = color(&args)?,
+            "Tf" => def.tf = color(&args)?,
+            "Ns" => def.ns = scalar(&args)?,
+            "Ni" => def.ni = scalar(&args)?,
+            "d" => def.d = scalar(&args)?,
+            "Tr" => def.d = 1.0 - scalar(&args)?,
+            "illum" => def.illum = scalar(&args)? as u32,
+            "map_Kd" => def.map_kd = Some(texture_path(&args)?),
+            "map_Ke" => def.map_ke = Some(texture_path(&args)?),
+            "Pr" => def.pr = Some(scalar(&args)?),
+            "Pm" => def.pm = Some(scalar(&args)?),
+            "Ps" => def.ps = Some(scalar(&args)?),
+            "Pc" => def.pc = Some(scalar(&args)?),
+            "map_Pr" => def.map_pr = Some(texture_path(&args)?),
+            "map_Pm" => def.map_pm = Some(texture_path(&args)?),
+            // Ambient, bump maps and the rest have no counterpart here
+            _ => {}
+        }
+    }
+
+    let mut materials = HashMap::new();
+    for (name, def) in defs {
+        let material = mtl_material(&def, options).map_err(|m| error(def.line, format!("material `{}`: {}", name, m)))?;
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+
+// Texture statements may start with options (`-s 1 1 1 -clamp on file.png`); the file name is
+// everything after them, so names with spaces survive
+fn texture_file(args: &[&str]) -> Option<String> {
+    let mut rest = args;
+    while let Some((option, tail)) = rest.split_first() {
+        let values = match *option {
+            "-blendu" | "-blendv" | "-bm" | "-boost" | "-cc" | "-clamp" | "-imfchan" | "-texres" | "-type" => 1,
+            "-mm" => 2,
+            // One to three numbers
+            "-o" | "-s" | "-t" => tail.iter().take(3).take_while(|a| a.parse::<f64>().is_ok()).count(),
+            _ => break,
+        };
+        rest = tail.get(values..)?;
+    }
+    if rest.is_empty() { None } else { Some(rest.join(" ")) }
+}
+
+// MTL maps tile unless told otherwise
+fn load_image(path: &Path) -> Result<ImageTexture, String> {
+    ImageTexture::load(&path.to_string_lossy())
+        .map(|t| t.with_filter(TextureFilter::Bilinear).with_wrap(WrapMode::Repeat, WrapMode::Repeat))
+        .map_err(|e| format!("cannot load texture {}: {}", path.display(), e))
+}
+
+// Color maps are sRGB
+fn load_texture(path: &Path) -> Result<Arc<dyn Texture>, String> {
+    Ok(Arc::new(load_image(path)?))
+}
+
+// Roughness and metalness maps hold plain numbers
+fn load_data_texture(path: &Path) -> Result<Arc<dyn Texture>, String> {
+    Ok(Arc::new(load_image(path)?.as_data()))
+}
+
+#[inline]
+fn max_component(c: Color) -> f64 {
+    c.x().max(c.y()).max(c.z())
+}
+
+fn mtl_material(def: &MtlDef, options: &ObjOptions) -> Result<Arc<dyn Material>, String> {
+    let is_pbr = [def.pr, def.pm, def.ps, def.pc].iter().any(Option::is_some) || def.map_pr.is_some() || def.map_pm.is_some();
+    if is_pbr {
+        return Ok(Arc::new(mtl_principled(def)?));
+    }
+    if let Some(path) = &def.map_ke {
+        return Ok(Arc::new(DiffuseLight::from_texture(load_texture(path)?)));
+    }
+    if max_component(def.ke) > 0.0 {
+        return Ok(Arc::new(DiffuseLight::new(def.ke)));
+    }
+    if def.d < 1.0 || matches!(def.illum, 4 | 6 | 7 | 9) {
+        return Ok(Arc::new(Dielectric::new(def.ni).with_transmittance(def.tf, options.transmittance_distance)));
+    }
+    // Only on request: a plain illum 2 material with a bright Ks is glossy plastic, not metal
+    if def.illum == 3 {
+        // Phong exponent to a rough fuzz: Ns 0 is very rough, Ns 1000 a mirror
+        let fuzz = (2.0 / (def.ns + 2.0)).sqrt();
+        return Ok(Arc::new(Metal::new(def.ks, fuzz)));
+    }
+    match &def.map_kd {
+        Some(path) => Ok(Arc::new(Lambertian::from_texture(load_texture(path)?))),
+        None => Ok(Arc::new(Lambertian::new(def.kd))),
+    }
+}
+
+fn mtl_principled(def: &MtlDef) -> Result<Principled, String> {
+    // A map wins over the constant for the same parameter
+    let scalar = |value: Option<f64>, map: &Option<PathBuf>, default: f64| -> Result<Arc<dyn Texture>, String> {
+        match map {
+            Some(path) => load_data_texture(path),
+            None => Ok(Principled::constant(value.unwrap_or(default))),
+        }
+    };
+
+    let mut m = match &def.map_kd {
+        Some(path) => Principled::from_texture(load_texture(path)?),
+        None => Principled::new(def.kd),
+    };
+    m.metallic = scalar(def.pm, &def.map_pm, 0.0)?;
+    m.roughness = scalar(def.pr, &def.map_pr, 0.5)?;
+    m.sheen = Principled::constant(def.ps.unwrap_or(0.0));
+    m.clearcoat = Principled::constant(def.pc.unwrap_or(0.0));
+    m.transmission = Principled::constant((1.0 - def.d).clamp(0.0, 1.0));
+    m.emission = match &def.map_ke {
+        Some(path) => load_texture(path)?,
+        None => Arc::new(SolidColor::new(def.ke)),
+    };
+    m.ior = def.ni;
+    Ok(m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A scratch directory with the given files, unique per test
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rusty_raytracing_obj_{}_{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        dir
+    }
+
+    fn load_error(dir: &Path, name: &str) -> ObjError {
+        match load_obj(dir.join(name)) {
+            Ok(_) => panic!("{} should not load", name),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn errors_name_file_and_line() {
+        let dir = write_files("errors", &[
+            ("index.obj", "v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 4\n"),
+            ("number.obj", "v 0 0 0\nv 1 x 0\n"),
+            ("material.obj", "v 0 0 0\nusemtl nope\n"),
+            ("bad.mtl", "newmtl m\nKd 1 x 1\n"),
+            ("uses_bad.obj", "mtllib bad.mtl\n"),
+        ]);
+
+        let error = load_error(&dir, "index.obj");
+        assert!(matches!(error, ObjError::Parse { line: 5, .. }));
+        assert_eq!(error.to_string(), format!("{}:5: vertex index 4 out of range (have 3)", dir.join("index.obj").display()));
+
+        let error = load_error(&dir, "number.obj");
+        assert!(matches!(error, ObjError::Parse { line: 2, .. }));
+        assert!(error.to_string().starts_with(&format!("{}:2: ", dir.join("number.obj").display())));
+
+        let error = load_error(&dir, "material.obj");
+        assert!(matches!(error, ObjError::Parse { line: 2, .. }));
+
+        // Errors in a material file point into that file
+        let error = load_error(&dir, "uses_bad.obj");
+        assert!(error.to_string().starts_with(&format!("{}:2: ", dir.join("bad.mtl").display())), "{}", error);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn missing_mtl_is_an_io_error() {
+        let dir = write_files("missing_mtl", &[("model.obj", "mtllib nothere.mtl\n")]);
+        let error = load_error(&dir, "model.obj");
+        assert!(matches!(&error, ObjError::Io { path, .. } if path.ends_with("nothere.mtl")));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn several_mtl_files() {
+        let dir = write_files("several_mtl", &[
+            ("a.mtl", "newmtl red\nKd 0.8 0.1 0.1\n"),
+            ("b.mtl", "newmtl shiny\nillum 2\nKd 0.1 0.1 0.1\nKs 0.9 0.9 0.9\n"),
+            ("model.obj", "mtllib a.mtl b.mtl\nv -1 0 0\nv 1 0 0\nv 0 1 0\nv 0 -1 0\nusemtl red\nf 1 2 3\nusemtl shiny\nf 1 4 2\n"),
+        ]);
+        let meshes = load_obj(dir.join("model.obj")).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(meshes.objects().len(), 2);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn vertex_colors_are_ignored() {
+        let dir = write_files("vertex_colors", &[
+            ("model.obj", "v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 0 1 0 0 0 1\nv 0 0 1 1\nf 1 2 3\nf 1 2 4\n"),
+            ("too_many.obj", "v 0 0 0 1 0 0 1 0\n"),
+        ]);
+        let meshes = load_obj(dir.join("model.obj")).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(meshes.objects().len(), 1);
+        assert!(matches!(load_error(&dir, "too_many.obj"), ObjError::Parse { line: 1, .. }));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn texture_options_are_skipped() {
+        let file = |line: &str| texture_file(&line.split_whitespace().collect::<Vec<_>>());
+        assert_eq!(file("wood.png"), Some("wood.png".to_string()));
+        assert_eq!(file("-s 2 2 1 -clamp on old wood.png"), Some("old wood.png".to_string()));
+        assert_eq!(file("-o 0.5 -mm 0 1 -bm 2 bricks.png"), Some("bricks.png".to_string()));
+        assert_eq!(file("-clamp on"), None);
+        assert_eq!(file("-mm 0"), None);
+    }
+
+    #[test]
+    fn texture_names_with_spaces() {
+        let dir = write_files("texture_spaces", &[
+            ("tex.mtl", "newmtl wood\nmap_Kd -s 1 1 1 old wood.png\n"),
+            ("model.obj", "mtllib tex.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl wood\nf 1 2 3\n"),
+        ]);
+        image::RgbImage::from_pixel(2, 2, image::Rgb([200, 120, 40])).save(dir.join("old wood.png")).unwrap();
+        let meshes = load_obj(dir.join("model.obj")).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(meshes.objects().len(), 1);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,58 @@
+// onb.rs
+// Orthonormal basis around a direction, for sampling directions in a local frame
+
+use crate::vec3::Vec3;
+
+#[derive(Copy, Clone, Debug)]
+pub struct ONB {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl ONB {
+    // Basis with w along n
+    pub fn new(n: Vec3) -> Self {
+        let w = n.unit_vector();
+        let a = if w.x().abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let v = w.cross(a).unit_vector();
+        let u = w.cross(v);
+        Self { u, v, w }
+    }
+
+    // Basis with w along n and u as close as possible to the tangent t
+    pub fn from_normal_tangent(n: Vec3, t: Vec3) -> Self {
+        let w = n.unit_vector();
+        let u = t - w * w.dot(t);
+        if u.length_squared() < 1e-12 {
+            return Self::new(n);
+        }
+        let u = u.unit_vector();
+        let v = w.cross(u);
+        Self { u, v, w }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    // Local (u, v, w) coordinates to world space
+    #[inline]
+    pub fn transform(&self, local: Vec3) -> Vec3 {
+        self.u * local.x() + self.v * local.y() + self.w * local.z()
+    }
+
+    // World space to local (u, v, w) coordinates
+    #[inline]
+    pub fn to_local(&self, world: Vec3) -> Vec3 {
+        Vec3::new(world.dot(self.u), world.dot(self.v), world.dot(self.w))
+    }
+}
@@ -8,7 +8,9 @@ use crate::hittable::{Hittable, HitRecord};
 use crate::hittable_list::HittableList;
 use crate::interval::Interval;
 use crate::ray::Ray;
+use crate::utils::prelude::random_f64;
 
+#[derive(Clone)]
 pub struct Quad {
     q: Point3,
     u: Vec3,
@@ -18,6 +20,7 @@ pub struct Quad {
     normal: Vec3,
     D: f64,
     w: Vec3,
+    area: f64,
 }
 
 impl Quad {
@@ -30,8 +33,9 @@ impl Quad {
         let normal = n.unit_vector();
         let D = normal.dot(q);
         let w = n / n.dot(n);
+        let area = n.length();
 
-        Self { q, u, v, material, bbox, normal, D, w }
+        Self { q, u, v, material, bbox, normal, D, w, area }
     }
 
 
@@ -91,8 +95,9 @@ impl Hittable for Quad {
 
         rec.t = t;
         rec.p = intersection;
-        rec.material = Some(self.material.as_ref());
+        rec.set_material(self.material.as_ref());
         rec.set_face_normal(r, self.normal);
+        rec.dpdu = Some(self.u);
 
         return true;
     }
@@ -101,4 +106,68 @@ impl Hittable for Quad {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    // Uniform over the area, converted to solid angle: distance^2 / (cos * area)
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let mut rec = HitRecord::new();
+        if !self.hit(&Ray::new(origin, direction), Interval::new(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(rec.normal) / direction.length()).abs();
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let p = self.q + (self.u * random_f64()) + (self.v * random_f64());
+        p - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::utils::prelude::PI;
+    use crate::vec3::Color;
+
+    const SAMPLES: usize = 1_000_000;
+
+    fn quad() -> Quad {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Quad::new(Point3::new(-1.0, 2.0, -0.5), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.5, 0.5, 1.5), material)
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        // Monte Carlo over uniform directions: the mean of pdf / (1 / 4 pi) is the integral
+        let quad = quad();
+        let origin = Point3::new(0.2, 1.4, 0.1);
+        let sum: f64 = (0..SAMPLES).map(|_| quad.pdf_value(origin, Vec3::random_unit_vector())).sum();
+        let integral = sum * 4.0 * PI / SAMPLES as f64;
+        assert!((integral - 1.0).abs() < 0.02, "pdf integrates to {}", integral);
+    }
+
+    #[test]
+    fn random_matches_pdf() {
+        // Every sample must hit, and the mean of 1 / pdf over samples is the solid angle,
+        // which uniform directions measure independently
+        let quad = quad();
+        let origin = Point3::new(0.2, 1.4, 0.1);
+        let mut inverse_pdf = 0.0;
+        for _ in 0..SAMPLES {
+            let pdf = quad.pdf_value(origin, quad.random(origin));
+            assert!(pdf > 0.0, "sampled direction misses the quad");
+            inverse_pdf += 1.0 / pdf;
+        }
+        let solid_angle = inverse_pdf / SAMPLES as f64;
+
+        let mut rec = HitRecord::new();
+        let hits = (0..SAMPLES)
+            .filter(|_| quad.hit(&Ray::new(origin, Vec3::random_unit_vector()), Interval::new(0.001, f64::INFINITY), &mut rec))
+            .count();
+        let expected = hits as f64 / SAMPLES as f64 * 4.0 * PI;
+        assert!((solid_angle - expected).abs() < 0.02 * expected, "solid angle {} vs {}", solid_angle, expected);
+    }
 }
@@ -8,6 +8,8 @@ pub struct Ray {
     direction: Vec3,
     inv_direction: Vec3,
     time: f64,
+    wavelength: Option<f64>, // nm, once a dispersive surface has split the path off; None is RGB
+    ignores_media: bool, // medium boundaries are transparent to it, to find the surface behind one
 }
 
 pub fn point_to_ray(point: Point3) -> Ray {
@@ -18,18 +20,20 @@ pub fn point_to_ray(point: Point3) -> Ray {
         direction,
         inv_direction,
         time: 0.0,
+        wavelength: None,
+        ignores_media: false,
     }
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Ray {
         let inv_direction = Vec3::new(1.0 / direction.x(), 1.0 / direction.y(), 1.0 / direction.z());
-        Ray { origin, direction, inv_direction, time: 0.0 }
+        Ray { origin, direction, inv_direction, time: 0.0, wavelength: None, ignores_media: false }
     }
 
     pub fn new_time(origin: Point3, direction: Vec3, time: f64) -> Ray {
         let inv_direction = Vec3::new(1.0 / direction.x(), 1.0 / direction.y(), 1.0 / direction.z());
-        Ray { origin, direction, inv_direction, time }
+        Ray { origin, direction, inv_direction, time, wavelength: None, ignores_media: false }
     }
 
     pub fn origin(&self) -> Point3 {
@@ -51,4 +55,27 @@ impl Ray {
     pub fn time(&self) -> f64 {
         self.time
     }
+
+    pub fn wavelength(&self) -> Option<f64> {
+        self.wavelength
+    }
+
+    pub fn with_wavelength(mut self, wavelength: Option<f64>) -> Ray {
+        self.wavelength = wavelength;
+        self
+    }
+
+    pub fn ignores_media(&self) -> bool {
+        self.ignores_media
+    }
+
+    pub fn ignoring_media(mut self) -> Ray {
+        self.ignores_media = true;
+        self
+    }
+
+    // The same ray (time, wavelength) expressed in another frame
+    pub fn transformed(&self, origin: Point3, direction: Vec3) -> Ray {
+        Ray { origin, direction, inv_direction: Vec3::new(1.0 / direction.x(), 1.0 / direction.y(), 1.0 / direction.z()), ..*self }
+    }
 }
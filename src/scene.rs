@@ -0,0 +1,28 @@
+// scene.rs
+
+use crate::bvh::SplitMethod;
+use crate::camera::Camera;
+use crate::framebuffer::FrameBuffer;
+use crate::hittable_list::HittableList;
+use crate::linear_bvh::LinearBVH;
+
+// A world plus the camera looking at it
+pub struct Scene {
+    pub world: HittableList,
+    pub camera: Camera,
+    pub lights: HittableList, // emitters (also in the world) to sample directly
+    pub bvh: SplitMethod, // how the BVH over the world is built
+}
+
+impl Scene {
+    pub fn new(world: HittableList, camera: Camera) -> Self {
+        Self { world, camera, lights: HittableList::new(), bvh: SplitMethod::SAH }
+    }
+
+    // Build a BVH over the world and render it
+    pub fn render(&mut self) -> FrameBuffer {
+        let (root, stats) = LinearBVH::build(&self.world, self.bvh);
+        eprintln!("BVH: {}", stats);
+        self.camera.render_with_lights(&root, &self.lights)
+    }
+}
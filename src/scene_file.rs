@@ -0,0 +1,979 @@
+// scene_file.rs
+// Loader for declarative TOML scene descriptions.
+//
+// A scene file has an optional [camera] table, named [textures.*] and [materials.*] tables
+// that objects refer to by name, and an [[objects]] array:
+//
+//   [camera]
+//   image_width = 600
+//   look_from = [278, 278, -800]
+//   look_at = [278, 278, 0]
+//   vfov = 40
+//   background = [0, 0, 0]
+//
+//   [textures.earth]
+//   type = "image"
+//   path = "textures/earthmap.jpg"      # relative to the scene file
+//
+//   [materials.white]
+//   type = "lambertian"
+//   albedo = [0.73, 0.73, 0.73]
+//
+//   [materials.globe]
+//   type = "lambertian"
+//   texture = "earth"
+//
+//   [[objects]]
+//   type = "box"
+//   a = [0, 0, 0]
+//   b = [165, 330, 165]
+//   material = "white"
+//   transform = [{ rotate_y = 15 }, { translate = [265, 0, 295] }]
+//
+//   [[objects]]
+//   type = "constant_medium"
+//   density = 0.01
+//   albedo = [1, 1, 1]
+//   boundary = { type = "sphere", center = [0, 0, 0], radius = 5000 }
+//
+// The `type` of each texture, material and object is the snake_case name of its variant in
+// TextureDef, MaterialDef and ObjectDef below, which also list the fields. Where a parameter
+// takes a number or color or else a texture, the texture goes in `<field>_texture` (just
+// `texture` for a material's albedo), never both.
+// Transforms (rotate_y, translate) are applied in the order listed.
+// Top-level spheres and quads with a diffuse_light material are also sampled directly as lights.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::camera::Camera;
+use crate::constant_medium::constant_medium;
+use crate::density::{DensityField, DensityGrid, NoiseDensity, TextureDensity};
+use crate::heterogeneous_medium::HeterogeneousMedium;
+use crate::hittable::{Hittable, RotateY, Translate};
+use crate::hittable_list::{HittableList, HittablePtr};
+use crate::linear_bvh::LinearBVH;
+use crate::medium::{Medium, MediumInterface, HomogeneousMedium, ChromaticMedium};
+use crate::material::{Material, Lambertian, OrenNayar, Metal, Conductor, Dielectric, RoughDielectric, Principled, Coated, MixMaterial, DiffuseLight, Isotropic, HenyeyGreenstein};
+use crate::quad::Quad;
+use crate::ray::Ray;
+use crate::scene::Scene;
+use crate::spectrum::Ior;
+use crate::sphere::Sphere;
+use crate::triangle::{Triangle, TriangleMesh};
+use crate::obj_loader::{self, ObjOptions};
+use crate::texture::{Texture, SolidColor, CheckerTexture, ImageTexture, NoiseTexture, TextureFilter, WrapMode};
+use crate::vec3::{Color, Vec3};
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io { path: PathBuf, source: io::Error },
+    // The file is not valid TOML or does not match the schema
+    Syntax { line: usize, column: usize, message: String },
+    // The file parsed but describes something we cannot build (unknown names, bad values, missing images)
+    Invalid { line: usize, column: usize, message: String },
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneFileError::Io { source, .. } => write!(f, "cannot read scene file: {}", source),
+            SceneFileError::Syntax { line, column, message } => write!(f, "line {}, column {}: {}", line, column, message),
+            SceneFileError::Invalid { line, column, message } => write!(f, "line {}, column {}: {}", line, column, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneFileError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// Read and build a scene file. Relative image paths are resolved against the file's directory.
+pub fn load_scene<P: AsRef<Path>>(path: P) -> Result<Scene, SceneFileError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|source| SceneFileError::Io { path: path.to_path_buf(), source })?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    parse_scene(&source, base_dir)
+}
+
+// Build a scene from TOML source text
+pub fn parse_scene(source: &str, base_dir: &Path) -> Result<Scene, SceneFileError> {
+    let def: SceneDef = toml::from_str(source).map_err(|e| {
+        let (line, column) = e.span().map_or((1, 1), |span| line_col(source, span.start));
+        SceneFileError::Syntax { line, column, message: e.message().trim_end().to_string() }
+    })?;
+
+    let mut builder = Builder {
+        source,
+        base_dir,
+        texture_defs: &def.textures,
+        material_defs: &def.materials,
+        textures: BTreeMap::new(),
+        materials: BTreeMap::new(),
+    };
+
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+    for object in &def.objects {
+        let hittable: HittablePtr = Arc::from(builder.object(object.get_ref(), object.span(), true)?);
+        if builder.is_light(object.get_ref()) {
+            lights.add_shared(hittable.clone());
+        }
+        world.add_shared(hittable);
+    }
+
+    let mut scene = Scene::new(world, def.camera.build());
+    scene.lights = lights;
+    Ok(scene)
+}
+
+// 1-based line and column of a byte offset
+fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, column)
+}
+
+// Serialized form
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDef {
+    #[serde(default)]
+    camera: CameraDef,
+    #[serde(default)]
+    textures: BTreeMap<String, Spanned<TextureDef>>,
+    #[serde(default)]
+    materials: BTreeMap<String, Spanned<MaterialDef>>,
+    #[serde(default)]
+    objects: Vec<Spanned<ObjectDef>>,
+}
+
+// Every field is optional and falls back to Camera::new()
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CameraDef {
+    aspect_ratio: Option<f64>,
+    image_width: Option<u32>,
+    samples_per_pixel: Option<u32>,
+    max_depth: Option<u32>,
+    max_diffuse_depth: Option<u32>,
+    max_specular_depth: Option<u32>,
+    max_volume_depth: Option<u32>,
+    russian_roulette_depth: Option<u32>,
+    vfov: Option<f64>,
+    look_from: Option<[f64; 3]>,
+    look_at: Option<[f64; 3]>,
+    vup: Option<[f64; 3]>,
+    defocus_angle: Option<f64>,
+    focus_distance: Option<f64>,
+    background: Option<[f64; 3]>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDef {
+    Solid { color: [f64; 3] },
+    // 3D checkerboard of cells `scale` wide
+    Checker {
+        scale: f64,
+        even: Option<[f64; 3]>,
+        odd: Option<[f64; 3]>,
+        even_texture: Option<String>,
+        odd_texture: Option<String>,
+    },
+    Image {
+        path: String, // relative to the scene file
+        filter: Option<String>, // nearest (default), bilinear or bicubic
+        wrap: Option<String>,   // clamp (default), repeat, mirror or black, for both axes
+        wrap_u: Option<String>,
+        wrap_v: Option<String>,
+        scale: Option<[f64; 2]>, // UV transform: scale, rotate (degrees) about the middle, offset
+        offset: Option<[f64; 2]>,
+        rotation: Option<f64>,
+        #[serde(default)]
+        linear: bool, // a data map (roughness, metalness, masks) rather than sRGB color
+    },
+    Noise { scale: f64 }, // Perlin marble
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDef {
+    Lambertian { albedo: Option<[f64; 3]>, texture: Option<String> },
+    // Rough diffuse; sigma 0 is Lambertian
+    OrenNayar {
+        albedo: Option<[f64; 3]>,
+        texture: Option<String>,
+        sigma: Option<f64>, // facet slope deviation in radians
+        sigma_texture: Option<String>,
+    },
+    Metal { albedo: [f64; 3], #[serde(default)] fuzz: f64 },
+    // GGX metal with Fresnel from a complex index of refraction
+    Conductor {
+        preset: Option<String>, // gold, silver, copper or aluminium, instead of eta and k
+        eta: Option<[f64; 3]>,
+        k: Option<[f64; 3]>,
+        roughness: Option<f64>,
+        roughness_texture: Option<String>,
+        roughness_v: Option<f64>, // anisotropic: roughness is then along u only
+        roughness_v_texture: Option<String>,
+    },
+    // Exactly one of refraction_index, glass, cauchy or sellmeier_b/sellmeier_c; tinted by
+    // absorption per unit length, or by transmittance
+    Dielectric {
+        refraction_index: Option<f64>,
+        // Dispersive glass instead of a single refraction_index: a named glass, or coefficients
+        glass: Option<String>, // bk7, sf11, fused_silica, diamond or water
+        cauchy: Option<[f64; 2]>, // a, b with wavelengths in micrometers
+        sellmeier_b: Option<[f64; 3]>,
+        sellmeier_c: Option<[f64; 3]>, // micrometers squared
+        absorption: Option<[f64; 3]>,
+        transmittance: Option<[f64; 3]>, // colored glass: what is left after transmittance_distance
+        transmittance_distance: Option<f64>,
+    },
+    // Frosted glass
+    RoughDielectric { refraction_index: f64, roughness: Option<f64>, roughness_texture: Option<String> },
+    // Disney-style uber material; every parameter but ior may be a texture
+    Principled {
+        base_color: Option<[f64; 3]>,
+        base_color_texture: Option<String>,
+        metallic: Option<f64>,
+        metallic_texture: Option<String>,
+        roughness: Option<f64>,
+        roughness_texture: Option<String>,
+        specular: Option<f64>,
+        specular_texture: Option<String>,
+        specular_tint: Option<f64>,
+        specular_tint_texture: Option<String>,
+        sheen: Option<f64>,
+        sheen_texture: Option<String>,
+        clearcoat: Option<f64>,
+        clearcoat_texture: Option<String>,
+        transmission: Option<f64>,
+        transmission_texture: Option<String>,
+        emission: Option<[f64; 3]>,
+        emission_texture: Option<String>,
+        ior: Option<f64>,
+    },
+    // A dielectric clear coat over another material, tinted by absorption or tint over thickness
+    Coated {
+        base: String, // another material
+        refraction_index: Option<f64>,
+        roughness: Option<f64>,
+        roughness_texture: Option<String>,
+        absorption: Option<[f64; 3]>,
+        tint: Option<[f64; 3]>, // transmittance of one crossing, instead of absorption
+        thickness: Option<f64>,
+    },
+    // Two other materials blended per point
+    Mix {
+        a: String,
+        b: String,
+        mask: Option<f64>, // 0 is all `a`, 1 all `b`
+        mask_texture: Option<String>,
+    },
+    DiffuseLight { emit: Option<[f64; 3]>, texture: Option<String> },
+    Isotropic { albedo: Option<[f64; 3]>, texture: Option<String> },
+    HenyeyGreenstein { albedo: Option<[f64; 3]>, texture: Option<String>, g: f64 }, // g > 0 scatters forward
+    // Fills objects made of it with `medium`; their surface scatters like `surface`, or is
+    // invisible without one (a glass sphere of smoke inside a fog, say). A glass object plus a
+    // constant_medium with the same boundary works the same
+    MediumInterface {
+        surface: Option<String>, // another material; without one the boundary is invisible
+        medium: MediumDef,
+    },
+}
+
+// A homogeneous medium: `density` and `albedo` (or `texture`), or per-channel
+// `sigma_a`/`sigma_s`; optional `emission` per unit length
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MediumDef {
+    density: Option<f64>,
+    albedo: Option<[f64; 3]>,
+    texture: Option<String>,
+    sigma_a: Option<[f64; 3]>, // per-channel coefficients, instead of density and albedo
+    sigma_s: Option<[f64; 3]>,
+    emission: Option<[f64; 3]>,
+    g: Option<f64>, // Henyey-Greenstein asymmetry; isotropic without it
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDef {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        velocity: Option<[f64; 3]>, // moving sphere: center at time t is center + t * velocity
+        material: Option<String>,
+        #[serde(default)]
+        transform: Vec<TransformDef>,
+    },
+    Quad {
+        q: [f64; 3],
+        u: [f64; 3],
+        v: [f64; 3],
+        material: Option<String>,
+        #[serde(default)]
+        transform: Vec<TransformDef>,
+    },
+    Box {
+        a: [f64; 3],
+        b: [f64; 3],
+        material: Option<String>,
+        #[serde(default)]
+        transform: Vec<TransformDef>,
+    },
+    Triangle {
+        a: [f64; 3],
+        b: [f64; 3],
+        c: [f64; 3],
+        normals: Option<[[f64; 3]; 3]>,
+        uvs: Option<[[f64; 2]; 3]>,
+        material: Option<String>,
+        #[serde(default)]
+        transform: Vec<TransformDef>,
+    },
+    // Inline indexed triangles; normals and uvs, if given, are per vertex
+    Mesh {
+        positions: Vec<[f64; 3]>,
+        triangles: Vec<[u32; 3]>,
+        normals: Option<Vec<[f64; 3]>>,
+        uvs: Option<Vec<[f64; 2]>>,
+        material: Option<String>,
+        #[serde(default)]
+        transform: Vec<TransformDef>,
+    },
+    // A Wavefront OBJ file, with the materials of its MTL files
+    Obj {
+        path: String,               // relative to the scene file
+        material: Option<String>,   // overrides the file's own materials
+        transmittance_distance: Option<f64>, // model units over which glass keeps its `Tf` color
+        #[serde(default)]
+        transform: Vec<TransformDef>,
+    },
+    // A homogeneous medium filling `boundary`, which may be any closed shape, nest in other
+    // media or enclose the camera
+    ConstantMedium {
+        boundary: Box<ObjectDef>,
+        // Same fields as MediumDef
+        density: Option<f64>,
+        albedo: Option<[f64; 3]>,
+        texture: Option<String>,
+        sigma_a: Option<[f64; 3]>,
+        sigma_s: Option<[f64; 3]>,
+        emission: Option<[f64; 3]>,
+        g: Option<f64>,
+        #[serde(default)]
+        transform: Vec<TransformDef>,
+    },
+    // A medium whose density is one of a grid stretched over the boundary box, Perlin turbulence
+    // (zero below noise_cutoff) or a texture. Transforms move the density with the boundary
+    HeterogeneousMedium {
+        boundary: Box<ObjectDef>,
+        density: f64,                   // scales the field's 0..1 (or grid) values
+        grid: Option<String>,           // .vol or raw float32 file, relative to the scene file
+        resolution: Option<[usize; 3]>, // for raw grids
+        noise_frequency: Option<f64>,
+        #[serde(default)]
+        noise_cutoff: f64,
+        density_texture: Option<String>,
+        albedo: Option<[f64; 3]>,
+        texture: Option<String>,
+        emission: Option<[f64; 3]>, // per unit density
+        g: Option<f64>,
+        #[serde(default)]
+        transform: Vec<TransformDef>,
+    },
+    // Objects sharing one transform, optionally under their own BVH
+    Group {
+        objects: Vec<ObjectDef>,
+        #[serde(default)]
+        bvh: bool,
+        #[serde(default)]
+        transform: Vec<TransformDef>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TransformDef {
+    RotateY(f64), // degrees
+    Translate([f64; 3]),
+}
+
+impl CameraDef {
+    fn build(&self) -> Camera {
+        let mut camera = Camera::new();
+        if let Some(x) = self.aspect_ratio { camera.aspect_ratio = x; }
+        if let Some(x) = self.image_width { camera.image_width = x; }
+        if let Some(x) = self.samples_per_pixel { camera.samples_per_pixel = x; }
+        if let Some(x) = self.max_depth { camera.max_depth = x; }
+        if let Some(x) = self.max_diffuse_depth { camera.max_diffuse_depth = x; }
+        if let Some(x) = self.max_specular_depth { camera.max_specular_depth = x; }
+        if let Some(x) = self.max_volume_depth { camera.max_volume_depth = x; }
+        if let Some(x) = self.russian_roulette_depth { camera.russian_roulette_depth = x; }
+        if let Some(x) = self.vfov { camera.vfov = x; }
+        if let Some(x) = self.look_from { camera.look_from = vec3(x); }
+        if let Some(x) = self.look_at { camera.look_at = vec3(x); }
+        if let Some(x) = self.vup { camera.vup = vec3(x); }
+        if let Some(x) = self.defocus_angle { camera.defocus_angle = x; }
+        if let Some(x) = self.focus_distance { camera.focus_distance = x; }
+        if let Some(x) = self.background { camera.background_color = vec3(x); }
+        camera
+    }
+}
+
+impl ObjectDef {
+    fn transform(&self) -> &[TransformDef] {
+        match self {
+            ObjectDef::Sphere { transform, .. }
+            | ObjectDef::Quad { transform, .. }
+            | ObjectDef::Box { transform, .. }
+            | ObjectDef::Triangle { transform, .. }
+            | ObjectDef::Mesh { transform, .. }
+            | ObjectDef::Obj { transform, .. }
+            | ObjectDef::ConstantMedium { transform, .. }
+            | ObjectDef::HeterogeneousMedium { transform, .. }
+            | ObjectDef::Group { transform, .. } => transform,
+        }
+    }
+}
+
+#[inline]
+fn vec3(a: [f64; 3]) -> Vec3 {
+    Vec3::new(a[0], a[1], a[2])
+}
+
+// Turns the definitions into objects, creating each named texture and material once
+struct Builder<'s> {
+    source: &'s str,
+    base_dir: &'s Path,
+    texture_defs: &'s BTreeMap<String, Spanned<TextureDef>>,
+    material_defs: &'s BTreeMap<String, Spanned<MaterialDef>>,
+    textures: BTreeMap<String, Arc<dyn Texture>>,
+    materials: BTreeMap<String, Arc<dyn Material>>,
+}
+
+impl Builder<'_> {
+    fn error(&self, span: Range<usize>, message: String) -> SceneFileError {
+        let (line, column) = line_col(self.source, span.start);
+        SceneFileError::Invalid { line, column, message }
+    }
+
+    fn texture(&mut self, name: &str, span: Range<usize>) -> Result<Arc<dyn Texture>, SceneFileError> {
+        self.texture_at_depth(name, span, 0)
+    }
+
+    fn texture_at_depth(&mut self, name: &str, span: Range<usize>, depth: usize) -> Result<Arc<dyn Texture>, SceneFileError> {
+        if let Some(t) = self.textures.get(name) {
+            return Ok(t.clone());
+        }
+        let Some(def) = self.texture_defs.get(name) else {
+            return Err(self.error(span, format!("unknown texture `{}`", name)));
+        };
+        // Checker textures can nest, guard against a texture containing itself
+        if depth > self.texture_defs.len() {
+            return Err(self.error(def.span(), format!("texture `{}` refers to itself", name)));
+        }
+
+        let span = def.span();
+        let texture: Arc<dyn Texture> = match def.get_ref() {
+            TextureDef::Solid { color } => Arc::new(SolidColor::new(vec3(*color))),
+            TextureDef::Checker { scale, even, odd, even_texture, odd_texture } => {
+                let even = self.color_or_texture("even", *even, even_texture.as_deref(), span.clone(), depth)?;
+                let odd = self.color_or_texture("odd", *odd, odd_texture.as_deref(), span.clone(), depth)?;
+                Arc::new(CheckerTexture::new(*scale, even, odd))
+            }
+            TextureDef::Image { path, filter, wrap, wrap_u, wrap_v, scale, offset, rotation, linear } => {
+                let filter = match filter.as_deref() {
+                    None | Some("nearest") => TextureFilter::Nearest,
+                    Some("bilinear") => TextureFilter::Bilinear,
+                    Some("bicubic") => TextureFilter::Bicubic,
+                    Some(name) => return Err(self.error(span, format!("unknown filter `{}` (nearest, bilinear, bicubic)", name))),
+                };
+                let wrap = self.wrap_mode(wrap.as_deref(), WrapMode::Clamp, span.clone())?;
+                let wrap_u = self.wrap_mode(wrap_u.as_deref(), wrap, span.clone())?;
+                let wrap_v = self.wrap_mode(wrap_v.as_deref(), wrap, span.clone())?;
+                let [su, sv] = scale.unwrap_or([1.0, 1.0]);
+                let [ou, ov] = offset.unwrap_or([0.0, 0.0]);
+
+                let full_path = self.base_dir.join(path);
+                let image = ImageTexture::load(&full_path.to_string_lossy())
+                    .map_err(|e| self.error(span.clone(), format!("cannot load image `{}`: {}", full_path.display(), e)))?;
+                let image = image.with_filter(filter).with_wrap(wrap_u, wrap_v).with_transform((su, sv), (ou, ov), rotation.unwrap_or(0.0));
+                Arc::new(if *linear { image.as_data() } else { image })
+            }
+            TextureDef::Noise { scale } => Arc::new(NoiseTexture::new(*scale)),
+        };
+
+        self.textures.insert(name.to_string(), texture.clone());
+        Ok(texture)
+    }
+
+    fn wrap_mode(&self, name: Option<&str>, default: WrapMode, span: Range<usize>) -> Result<WrapMode, SceneFileError> {
+        match name {
+            None => Ok(default),
+            Some("repeat") => Ok(WrapMode::Repeat),
+            Some("clamp") => Ok(WrapMode::Clamp),
+            Some("mirror") => Ok(WrapMode::Mirror),
+            Some("black") => Ok(WrapMode::Black),
+            Some(name) => Err(self.error(span, format!("unknown wrap mode `{}` (repeat, clamp, mirror, black)", name))),
+        }
+    }
+
+    // Exactly one of a literal color or a texture name
+    fn color_or_texture(&mut self, field: &str, color: Option<[f64; 3]>, texture: Option<&str>, span: Range<usize>, depth: usize) -> Result<Arc<dyn Texture>, SceneFileError> {
+        match (color, texture) {
+            (Some(c), None) => Ok(Arc::new(SolidColor::new(vec3(c)))),
+            (None, Some(name)) => self.texture_at_depth(name, span, depth + 1),
+            (Some(_), Some(_)) => Err(self.error(span, format!("give either `{0}` or `{0}_texture`, not both", field))),
+            (None, None) => Err(self.error(span, format!("missing `{0}` or `{0}_texture`", field))),
+        }
+    }
+
+    // Same as color_or_texture for the material fields named `<field>` and `texture`
+    fn material_texture(&mut self, field: &str, color: Option<[f64; 3]>, texture: Option<&str>, span: Range<usize>) -> Result<Arc<dyn Texture>, SceneFileError> {
+        match (color, texture) {
+            (Some(c), None) => Ok(Arc::new(SolidColor::new(vec3(c)))),
+            (None, Some(name)) => self.texture(name, span),
+            (Some(_), Some(_)) => Err(self.error(span, format!("give either `{}` or `texture`, not both", field))),
+            (None, None) => Err(self.error(span, format!("missing `{}` or `texture`", field))),
+        }
+    }
+
+    // Scalar parameter given as a number or a texture name, with a default when neither is set
+    fn scalar_or_texture(&mut self, field: &str, value: Option<f64>, texture: Option<&str>, default: f64, span: Range<usize>) -> Result<Arc<dyn Texture>, SceneFileError> {
+        match (value, texture) {
+            (Some(_), Some(_)) => Err(self.error(span, format!("give either `{0}` or `{0}_texture`, not both", field))),
+            (None, Some(name)) => self.texture(name, span),
+            (value, None) => {
+                let x = value.unwrap_or(default);
+                Ok(Arc::new(SolidColor::new(Color::new(x, x, x))))
+            }
+        }
+    }
+
+    fn conductor(&mut self, def: &MaterialDef, span: Range<usize>) -> Result<Conductor, SceneFileError> {
+        let MaterialDef::Conductor { preset, eta, k, roughness, roughness_texture, roughness_v, roughness_v_texture } = def else {
+            unreachable!("conductor() called with another material");
+        };
+
+        let conductor = match (preset.as_deref(), eta, k) {
+            (Some("gold"), None, None) => Conductor::gold(0.0),
+            (Some("silver"), None, None) => Conductor::silver(0.0),
+            (Some("copper"), None, None) => Conductor::copper(0.0),
+            (Some("aluminium" | "aluminum"), None, None) => Conductor::aluminium(0.0),
+            (Some(name), None, None) => {
+                return Err(self.error(span, format!("unknown conductor preset `{}` (gold, silver, copper, aluminium)", name)));
+            }
+            (None, Some(eta), Some(k)) => Conductor::new(vec3(*eta), vec3(*k), 0.0),
+            _ => return Err(self.error(span, "conductor needs either `preset` or both `eta` and `k`".to_string())),
+        };
+
+        let roughness_u = self.scalar_or_texture("roughness", *roughness, roughness_texture.as_deref(), 0.0, span.clone())?;
+        let roughness_v = if roughness_v.is_some() || roughness_v_texture.is_some() {
+            self.scalar_or_texture("roughness_v", *roughness_v, roughness_v_texture.as_deref(), 0.0, span)?
+        } else {
+            roughness_u.clone()
+        };
+        Ok(conductor.with_roughness_textures(roughness_u, roughness_v))
+    }
+
+    fn ior(&self, def: &MaterialDef, span: Range<usize>) -> Result<Ior, SceneFileError> {
+        let MaterialDef::Dielectric { refraction_index, glass, cauchy, sellmeier_b, sellmeier_c, .. } = def else {
+            unreachable!("ior() called with another material");
+        };
+
+        match (refraction_index, glass.as_deref(), cauchy, sellmeier_b, sellmeier_c) {
+            (Some(n), None, None, None, None) => Ok(Ior::Constant(*n)),
+            (None, Some(name), None, None, None) => match name {
+                "bk7" => Ok(Ior::bk7()),
+                "sf11" => Ok(Ior::sf11()),
+                "fused_silica" => Ok(Ior::fused_silica()),
+                "diamond" => Ok(Ior::diamond()),
+                "water" => Ok(Ior::water()),
+                _ => Err(self.error(span, format!("unknown glass `{}` (bk7, sf11, fused_silica, diamond, water)", name))),
+            },
+            (None, None, Some([a, b]), None, None) => Ok(Ior::Cauchy { a: *a, b: *b }),
+            (None, None, None, Some(b), Some(c)) => Ok(Ior::Sellmeier { b: *b, c: *c }),
+            _ => Err(self.error(span, "dielectric needs one of `refraction_index`, `glass`, `cauchy`, or `sellmeier_b` with `sellmeier_c`".to_string())),
+        }
+    }
+
+    fn principled(&mut self, def: &MaterialDef, span: Range<usize>) -> Result<Principled, SceneFileError> {
+        let MaterialDef::Principled {
+            base_color, base_color_texture, metallic, metallic_texture, roughness, roughness_texture,
+            specular, specular_texture, specular_tint, specular_tint_texture, sheen, sheen_texture,
+            clearcoat, clearcoat_texture, transmission, transmission_texture, emission, emission_texture, ior,
+        } = def else {
+            unreachable!("principled() called with another material");
+        };
+
+        let base_color = match (base_color, base_color_texture) {
+            (None, None) => Arc::new(SolidColor::new(Color::new(0.8, 0.8, 0.8))),
+            (color, texture) => self.color_or_texture("base_color", *color, texture.as_deref(), span.clone(), 0)?,
+        };
+        let mut m = Principled::from_texture(base_color);
+        m.metallic = self.scalar_or_texture("metallic", *metallic, metallic_texture.as_deref(), 0.0, span.clone())?;
+        m.roughness = self.scalar_or_texture("roughness", *roughness, roughness_texture.as_deref(), 0.5, span.clone())?;
+        m.specular = self.scalar_or_texture("specular", *specular, specular_texture.as_deref(), 0.5, span.clone())?;
+        m.specular_tint = self.scalar_or_texture("specular_tint", *specular_tint, specular_tint_texture.as_deref(), 0.0, span.clone())?;
+        m.sheen = self.scalar_or_texture("sheen", *sheen, sheen_texture.as_deref(), 0.0, span.clone())?;
+        m.clearcoat = self.scalar_or_texture("clearcoat", *clearcoat, clearcoat_texture.as_deref(), 0.0, span.clone())?;
+        m.transmission = self.scalar_or_texture("transmission", *transmission, transmission_texture.as_deref(), 0.0, span.clone())?;
+        if emission.is_some() || emission_texture.is_some() {
+            m.emission = self.color_or_texture("emission", *emission, emission_texture.as_deref(), span, 0)?;
+        }
+        m.ior = ior.unwrap_or(1.5);
+        Ok(m)
+    }
+
+    fn material(&mut self, name: &str, span: Range<usize>) -> Result<Arc<dyn Material>, SceneFileError> {
+        self.material_at_depth(name, span, 0)
+    }
+
+    fn material_at_depth(&mut self, name: &str, span: Range<usize>, depth: usize) -> Result<Arc<dyn Material>, SceneFileError> {
+        if let Some(m) = self.materials.get(name) {
+            return Ok(m.clone());
+        }
+        let Some(def) = self.material_defs.get(name) else {
+            return Err(self.error(span, format!("unknown material `{}`", name)));
+        };
+        // Coated, mix and medium_interface materials wrap other materials, guard against one containing itself
+        if depth > self.material_defs.len() {
+            return Err(self.error(def.span(), format!("material `{}` refers to itself", name)));
+        }
+
+        let span = def.span();
+        let material: Arc<dyn Material> = match def.get_ref() {
+            MaterialDef::Lambertian { albedo, texture } => {
+                Arc::new(Lambertian::from_texture(self.material_texture("albedo", *albedo, texture.as_deref(), span)?))
+            }
+            MaterialDef::OrenNayar { albedo, texture, sigma, sigma_texture } => {
+                let albedo = self.material_texture("albedo", *albedo, texture.as_deref(), span.clone())?;
+                let sigma = self.scalar_or_texture("sigma", *sigma, sigma_texture.as_deref(), 0.0, span)?;
+                Arc::new(OrenNayar::from_textures(albedo, sigma))
+            }
+            MaterialDef::Metal { albedo, fuzz } => Arc::new(Metal::new(vec3(*albedo), *fuzz)),
+            conductor @ MaterialDef::Conductor { .. } => Arc::new(self.conductor(conductor, span)?),
+            ior @ MaterialDef::Dielectric { absorption, transmittance, transmittance_distance, .. } => {
+                let glass = Dielectric::from_ior(self.ior(ior, span.clone())?);
+                Arc::new(match (absorption, transmittance) {
+                    (Some(_), Some(_)) => return Err(self.error(span, "give either `absorption` or `transmittance`, not both".to_string())),
+                    (Some(a), None) => glass.with_absorption(vec3(*a)),
+                    (None, Some(t)) => glass.with_transmittance(vec3(*t), transmittance_distance.unwrap_or(1.0)),
+                    (None, None) => glass,
+                })
+            }
+            MaterialDef::RoughDielectric { refraction_index, roughness, roughness_texture } => {
+                let roughness = self.scalar_or_texture("roughness", *roughness, roughness_texture.as_deref(), 0.0, span)?;
+                Arc::new(RoughDielectric::from_texture(*refraction_index, roughness))
+            }
+            principled @ MaterialDef::Principled { .. } => Arc::new(self.principled(principled, span)?),
+            MaterialDef::Coated { base, refraction_index, roughness, roughness_texture, absorption, tint, thickness } => {
+                let base = self.material_at_depth(base, span.clone(), depth + 1)?;
+                let roughness = self.scalar_or_texture("roughness", *roughness, roughness_texture.as_deref(), 0.0, span.clone())?;
+                let coat = Coated::from_texture(base, refraction_index.unwrap_or(1.5), roughness);
+                let thickness = thickness.unwrap_or(1.0);
+                Arc::new(match (absorption, tint) {
+                    (Some(_), Some(_)) => return Err(self.error(span, "give either `absorption` or `tint`, not both".to_string())),
+                    (Some(a), None) => coat.with_absorption(vec3(*a), thickness),
+                    (None, Some(t)) => coat.with_tint(vec3(*t), thickness),
+                    (None, None) => coat,
+                })
+            }
+            MaterialDef::Mix { a, b, mask, mask_texture } => {
+                let a = self.material_at_depth(a, span.clone(), depth + 1)?;
+                let b = self.material_at_depth(b, span.clone(), depth + 1)?;
+                let mask = self.scalar_or_texture("mask", *mask, mask_texture.as_deref(), 0.5, span)?;
+                Arc::new(MixMaterial::from_texture(a, b, mask))
+            }
+            MaterialDef::DiffuseLight { emit, texture } => {
+                Arc::new(DiffuseLight::from_texture(self.material_texture("emit", *emit, texture.as_deref(), span)?))
+            }
+            MaterialDef::Isotropic { albedo, texture } => {
+                Arc::new(Isotropic::from_texture(self.material_texture("albedo", *albedo, texture.as_deref(), span)?))
+            }
+            MaterialDef::HenyeyGreenstein { albedo, texture, g } => {
+                Arc::new(HenyeyGreenstein::from_texture(self.material_texture("albedo", *albedo, texture.as_deref(), span)?, *g))
+            }
+            MaterialDef::MediumInterface { surface, medium } => {
+                let medium = self.medium(medium, span.clone())?;
+                match surface {
+                    Some(surface) => Arc::new(MediumInterface::new(self.material_at_depth(surface, span, depth + 1)?, medium)),
+                    None => Arc::new(MediumInterface::invisible(medium)),
+                }
+            }
+        };
+
+        self.materials.insert(name.to_string(), material.clone());
+        Ok(material)
+    }
+
+    // Top-level spheres and quads with a diffuse_light material are sampled directly as lights
+    fn is_light(&self, def: &ObjectDef) -> bool {
+        let material = match def {
+            ObjectDef::Sphere { material, .. } | ObjectDef::Quad { material, .. } => material,
+            _ => return false,
+        };
+        material
+            .as_ref()
+            .and_then(|name| self.material_defs.get(name))
+            .is_some_and(|m| matches!(m.get_ref(), MaterialDef::DiffuseLight { .. }))
+    }
+
+    // Objects used as a medium boundary don't need a material, they are never shaded
+    fn object_material(&mut self, material: &Option<String>, span: Range<usize>, required: bool) -> Result<Arc<dyn Material>, SceneFileError> {
+        match material {
+            Some(name) => self.material(name, span),
+            None if required => Err(self.error(span, "missing `material`".to_string())),
+            None => Ok(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))),
+        }
+    }
+
+    // Per-channel absorption and scattering for a constant_medium that needs the chromatic
+    // medium: one given `sigma_a`/`sigma_s`, or one that emits, converted from its gray density
+    // and albedo color. None for a plain gray medium
+    fn medium_coefficients(density: Option<f64>, albedo: Option<[f64; 3]>, textured: bool, sigma_a: Option<[f64; 3]>, sigma_s: Option<[f64; 3]>, emits: bool) -> Result<Option<(Color, Color)>, String> {
+        let (sigma_a, sigma_s) = match (density, sigma_a, sigma_s) {
+            (None, None, None) => return Err("medium needs a `density` or `sigma_a`/`sigma_s`".to_string()),
+            (Some(density), None, None) => {
+                if density <= 0.0 {
+                    return Err(format!("medium density must be positive, got {}", density));
+                }
+                if !emits {
+                    return Ok(None);
+                }
+                if textured {
+                    return Err("an emitting medium needs an `albedo` color, not a texture".to_string());
+                }
+                let albedo = albedo.map_or(Color::new(1.0, 1.0, 1.0), vec3);
+                ((Color::new(1.0, 1.0, 1.0) - albedo) * density, albedo * density)
+            }
+            (Some(_), _, _) => return Err("give a medium either `density` or `sigma_a`/`sigma_s`, not both".to_string()),
+            (None, a, s) => {
+                if albedo.is_some() || textured {
+                    return Err("`albedo` does not apply with `sigma_a`/`sigma_s`".to_string());
+                }
+                (a.map_or(Color::init_zero(), vec3), s.map_or(Color::init_zero(), vec3))
+            }
+        };
+        if [sigma_a, sigma_s].iter().any(|c| c.x() < 0.0 || c.y() < 0.0 || c.z() < 0.0) {
+            return Err("medium coefficients must not be negative".to_string());
+        }
+        Ok(Some((sigma_a, sigma_s)))
+    }
+
+    fn medium(&mut self, def: &MediumDef, span: Range<usize>) -> Result<Arc<dyn Medium>, SceneFileError> {
+        let MediumDef { density, albedo, texture, sigma_a, sigma_s, emission, g } = def;
+        let chromatic = Self::medium_coefficients(*density, *albedo, texture.is_some(), *sigma_a, *sigma_s, emission.is_some())
+            .map_err(|message| self.error(span.clone(), message))?;
+        if let Some((sigma_a, sigma_s)) = chromatic {
+            let mut medium = ChromaticMedium::new(sigma_a, sigma_s, g.unwrap_or(0.0));
+            if let Some(emission) = emission {
+                medium = medium.with_emission(vec3(*emission));
+            }
+            return Ok(Arc::new(medium));
+        }
+
+        let phase = self.material_texture("albedo", *albedo, texture.as_deref(), span)?;
+        let phase: Arc<dyn Material> = match g {
+            Some(g) => Arc::new(HenyeyGreenstein::from_texture(phase, *g)),
+            None => Arc::new(Isotropic::from_texture(phase)),
+        };
+        Ok(Arc::new(HomogeneousMedium::new(density.unwrap(), phase)))
+    }
+
+    fn object(&mut self, def: &ObjectDef, span: Range<usize>, needs_material: bool) -> Result<Box<dyn Hittable>, SceneFileError> {
+        let object: Box<dyn Hittable> = match def {
+            ObjectDef::Sphere { center, radius, velocity, material, .. } => {
+                if *radius <= 0.0 {
+                    return Err(self.error(span, format!("sphere radius must be positive, got {}", radius)));
+                }
+                let material = self.object_material(material, span, needs_material)?;
+                let center = Ray::new(vec3(*center), velocity.map_or(Vec3::init_zero(), vec3));
+                Box::new(Sphere::new(center, *radius, material))
+            }
+            ObjectDef::Quad { q, u, v, material, .. } => {
+                let (u, v) = (vec3(*u), vec3(*v));
+                if u.cross(v).length_squared() == 0.0 {
+                    return Err(self.error(span, "quad edges `u` and `v` must not be parallel".to_string()));
+                }
+                let material = self.object_material(material, span, needs_material)?;
+                Box::new(Quad::new(vec3(*q), u, v, material))
+            }
+            ObjectDef::Box { a, b, material, .. } => {
+                let material = self.object_material(material, span, needs_material)?;
+                Box::new(Quad::make_box(&vec3(*a), &vec3(*b), material))
+            }
+            ObjectDef::Triangle { a, b, c, normals, uvs, material, .. } => {
+                let material = self.object_material(material, span, needs_material)?;
+                let mut triangle = Triangle::new(vec3(*a), vec3(*b), vec3(*c), material);
+                if let Some([na, nb, nc]) = normals {
+                    triangle = triangle.with_normals(vec3(*na), vec3(*nb), vec3(*nc));
+                }
+                if let Some([ta, tb, tc]) = uvs {
+                    triangle = triangle.with_uvs((ta[0], ta[1]), (tb[0], tb[1]), (tc[0], tc[1]));
+                }
+                Box::new(triangle)
+            }
+            ObjectDef::Mesh { positions, triangles, normals, uvs, material, .. } => {
+                let material = self.object_material(material, span.clone(), needs_material)?;
+                let mesh = TriangleMesh::new(
+                    positions.iter().map(|p| vec3(*p)).collect(),
+                    normals.as_ref().map(|n| n.iter().map(|v| vec3(*v)).collect()),
+                    uvs.as_ref().map(|uv| uv.iter().map(|t| (t[0], t[1])).collect()),
+                    triangles.clone(),
+                    material,
+                );
+                Box::new(mesh.map_err(|message| self.error(span, message))?)
+            }
+            ObjectDef::Obj { path, material, transmittance_distance, .. } => {
+                let mut options = ObjOptions::new();
+                if let Some(name) = material {
+                    options.material_override = Some(self.material(name, span.clone())?);
+                }
+                if let Some(distance) = *transmittance_distance {
+                    if distance <= 0.0 {
+                        return Err(self.error(span, format!("transmittance_distance must be positive, got {}", distance)));
+                    }
+                    options.transmittance_distance = distance;
+                }
+                let meshes = obj_loader::load_obj_with(self.base_dir.join(path), &options)
+                    .map_err(|e| self.error(span, e.to_string()))?;
+                Box::new(LinearBVH::new(&meshes))
+            }
+            ObjectDef::ConstantMedium { boundary, density, albedo, texture, sigma_a, sigma_s, emission, g, .. } => {
+                let medium = MediumDef {
+                    density: *density,
+                    albedo: *albedo,
+                    texture: texture.clone(),
+                    sigma_a: *sigma_a,
+                    sigma_s: *sigma_s,
+                    emission: *emission,
+                    g: *g,
+                };
+                let medium = self.medium(&medium, span.clone())?;
+                let boundary: Arc<dyn Hittable> = Arc::from(self.object(boundary, span, false)?);
+                Box::new(constant_medium::from_medium(boundary, medium))
+            }
+            ObjectDef::HeterogeneousMedium { boundary, density, grid, resolution, noise_frequency, noise_cutoff, density_texture, albedo, texture, emission, g, .. } => {
+                if *density <= 0.0 {
+                    return Err(self.error(span, format!("medium density must be positive, got {}", density)));
+                }
+                let phase = self.material_texture("albedo", *albedo, texture.as_deref(), span.clone())?;
+                let boundary: Arc<dyn Hittable> = Arc::from(self.object(boundary, span.clone(), false)?);
+                let field: Arc<dyn DensityField> = match (grid, noise_frequency, density_texture) {
+                    (Some(path), None, None) => {
+                        let grid = DensityGrid::load(self.base_dir.join(path), *resolution, boundary.bounding_box())
+                            .map_err(|e| self.error(span.clone(), e.to_string()))?;
+                        Arc::new(grid)
+                    }
+                    (None, Some(frequency), None) => Arc::new(NoiseDensity::new(*frequency, *noise_cutoff)),
+                    (None, None, Some(name)) => Arc::new(TextureDensity::new(self.texture(name, span.clone())?)),
+                    _ => return Err(self.error(span, "medium needs exactly one of `grid`, `noise_frequency` or `density_texture`".to_string())),
+                };
+                let phase: Arc<dyn Material> = match g {
+                    Some(g) => Arc::new(HenyeyGreenstein::from_texture(phase, *g)),
+                    None => Arc::new(Isotropic::from_texture(phase)),
+                };
+                let mut medium = HeterogeneousMedium::new(field, *density, phase);
+                if let Some(emission) = emission {
+                    medium = medium.with_emission(vec3(*emission));
+                }
+                Box::new(constant_medium::from_medium(boundary, Arc::new(medium)))
+            }
+            ObjectDef::Group { objects, bvh, .. } => {
+                let mut list = HittableList::new();
+                for object in objects {
+                    list.add(self.object(object, span.clone(), needs_material)?);
+                }
+                if *bvh {
+                    Box::new(LinearBVH::new(&list))
+                } else {
+                    Box::new(list)
+                }
+            }
+        };
+
+        Ok(apply_transforms(object, def.transform()))
+    }
+}
+
+fn apply_transforms(mut object: Box<dyn Hittable>, transforms: &[TransformDef]) -> Box<dyn Hittable> {
+    for t in transforms {
+        object = match t {
+            TransformDef::RotateY(angle) => Box::new(RotateY::new(Arc::from(object), *angle)),
+            TransformDef::Translate(offset) => Box::new(Translate::new(Arc::from(object), vec3(*offset))),
+        };
+    }
+    object
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(source: &str) -> SceneFileError {
+        match parse_scene(source, Path::new(".")) {
+            Ok(_) => panic!("scene should not build"),
+            Err(e) => e,
+        }
+    }
+
+    fn position(error: &SceneFileError) -> (usize, usize) {
+        match error {
+            SceneFileError::Syntax { line, column, .. } | SceneFileError::Invalid { line, column, .. } => (*line, *column),
+            SceneFileError::Io { .. } => panic!("unexpected I/O error: {}", error),
+        }
+    }
+
+    #[test]
+    fn syntax_error_position() {
+        let error = parse_error("[camera]\nimage_width = 100\nvfov = \n");
+        assert!(matches!(error, SceneFileError::Syntax { .. }));
+        assert_eq!(position(&error), (3, 8));
+    }
+
+    #[test]
+    fn unknown_field_position() {
+        let error = parse_error("[camera]\nfov = 40\n");
+        assert!(matches!(error, SceneFileError::Syntax { .. }));
+        assert_eq!(position(&error), (2, 1));
+        assert!(error.to_string().contains("unknown field `fov`"));
+    }
+
+    #[test]
+    fn unknown_name_points_at_object() {
+        let source = "[camera]\nvfov = 40\n\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"missing\"\n";
+        let error = parse_error(source);
+        assert!(matches!(error, SceneFileError::Invalid { .. }));
+        assert_eq!(position(&error), (4, 1));
+        assert_eq!(error.to_string(), "line 4, column 1: unknown material `missing`");
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let source = "a = \"é\"\nbé = 1";
+        assert_eq!(line_col(source, 0), (1, 1));
+        assert_eq!(line_col(source, source.find('=').unwrap()), (1, 3));
+        assert_eq!(line_col(source, source.rfind('=').unwrap()), (2, 4));
+        assert_eq!(line_col(source, source.len() + 10), (2, 7));
+    }
+}
@@ -0,0 +1,549 @@
+// scenes.rs
+// Built-in demo scenes. Each returns the world and a camera configured for it;
+// the caller renders it (and may override the camera settings first).
+
+use std::sync::Arc;
+
+use crate::utils::prelude::*;
+use crate::scene::Scene;
+
+pub fn bouncing_spheres() -> Scene {
+
+    // World
+    let mut world = HittableList::new();
+
+    // Ground
+    //let material_ground = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let checkered_ground_texture = Arc::new(CheckerTexture::from_colors(0.32, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9)));
+    world.add(Box::new(Sphere::new(Ray::new(Point3::new(0.0, -1000.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 1000.0, Arc::new(Lambertian::from_texture(checkered_ground_texture)))));
+
+    // Spheres
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random_f64();
+            let center:Ray = Ray::new(Point3::new(a as f64 + 0.9*random_f64(), 0.2, b as f64 + 0.9*random_f64()), Vec3::new(0.0, 0.0, 0.0));
+            if (center.origin() - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let sphere_material: Arc<dyn Material>;
+                if choose_mat < 0.8 {
+                    // diffuse
+                    let albedo = Color::new(random_f64()*random_f64(), random_f64()*random_f64(), random_f64()*random_f64());
+                    sphere_material = Arc::new(Lambertian::new(albedo));
+                    let moving_center: Ray = Ray::new(center.origin(), Vec3::new(0.5, random_f64_range(0.0, 0.2), 0.0));
+                    world.add(Box::new(Sphere::new(moving_center, 0.2, sphere_material)));
+                } else if choose_mat < 0.95 {
+                    // metal
+                    let albedo = Color::new(random_f64_range(0.5, 1.0), random_f64_range(0.5, 1.0), random_f64_range(0.5, 1.0));
+                    let fuzz = random_f64_range(0.0, 0.5);
+                    sphere_material = Arc::new(Metal::new(albedo, fuzz));
+                    world.add(Box::new(Sphere::new(center, 0.2, sphere_material)));
+                } else {
+                    // glass
+                    sphere_material = Arc::new(Dielectric::new(1.5));
+                    world.add(Box::new(Sphere::new(center, 0.2, sphere_material)));
+                }
+            }
+        }
+    }
+
+    let material1 = Arc::new(Dielectric::new(1.5));
+    world.add(Box::new(Sphere::new(Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 1.0, material1)));
+
+    let material2 = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    world.add(Box::new(Sphere::new(Ray::new(Point3::new(-4.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 1.0, material2)));
+
+    let material3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    world.add(Box::new(Sphere::new(Ray::new(Point3::new(4.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 1.0, material3)));
+
+
+    // Camera
+    let mut camera = Camera::new();
+
+    camera.aspect_ratio = 1.0; //16.0 / 9.0;
+    camera.image_width = 600; //400;
+    camera.samples_per_pixel = 200; //50;
+    camera.max_depth = 50;
+    camera.vfov = 20.0;
+    camera.look_from = Point3::new(13.0, 2.0, 3.0);
+    camera.look_at = Point3::new(0.0, 0.0, 0.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    camera.defocus_angle = 0.6; // degrees
+    camera.focus_distance = 10.0;
+
+    camera.background_color = Color::new(0.7, 0.8, 1.0); // light blue background
+
+    Scene::new(world, camera)
+}
+
+
+pub fn checkered_sphere() -> Scene {
+    // World
+    let mut world = HittableList::new();
+
+    let checker = CheckerTexture::from_colors(0.32, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9));
+    world.add(Box::new(Sphere::new(Ray::new(Point3::new(0.0, -10.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 10.0, Arc::new(Lambertian::from_texture(Arc::new(checker.clone()))))));
+    world.add(Box::new(Sphere::new(Ray::new(Point3::new(0.0, 10.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 10.0, Arc::new(Lambertian::from_texture(Arc::new(checker.clone()))))));
+
+    // Camera
+    let mut camera = Camera::new();
+
+    camera.aspect_ratio = 16.0 / 9.0;
+    camera.image_width = 400;
+    camera.samples_per_pixel = 100;
+    camera.max_depth = 50;
+
+    camera.vfov = 20.0;
+    camera.look_from = Point3::new(13.0, 2.0, 3.0);
+    camera.look_at = Point3::new(0.0, 0.0, 0.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    camera.background_color = Color::new(0.7, 0.8, 1.0);
+
+    camera.defocus_angle = 0.0; // degrees
+    Scene::new(world, camera)
+}
+
+pub fn earth() -> Scene {
+    // World
+    let mut world = HittableList::new();
+
+    let earth_texture: Arc<dyn Texture> = Arc::new(ImageTexture::new("textures/earthmap.jpg")
+        .with_filter(TextureFilter::Bilinear)
+        .with_wrap(WrapMode::Repeat, WrapMode::Clamp)); // wraps around at the date line
+    let earth_surface = Arc::new(Lambertian::from_texture(earth_texture));
+    let globe = Sphere::new(Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 2.0, earth_surface);
+    world.add(Box::new(globe));
+
+    
+    // Camera
+    let mut camera = Camera::new();
+
+    camera.aspect_ratio = 16.0 / 9.0;
+    camera.image_width = 400;
+    camera.samples_per_pixel = 100;
+    camera.max_depth = 50;
+
+    camera.vfov = 20.0;
+    camera.look_from = Point3::new(0.0, 0.0, 12.0);
+    camera.look_at = Point3::new(0.0, 0.0, 0.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    camera.background_color = Color::new(0.7, 0.8, 1.0);
+
+    camera.defocus_angle = 0.0; // degrees
+    Scene::new(world, camera)
+}
+
+pub fn tinted_glass() -> Scene {
+    // World
+    let mut world = HittableList::new();
+
+    let ground = Arc::new(Lambertian::from_texture(Arc::new(CheckerTexture::from_colors(0.5, Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9)))));
+    world.add(Box::new(Sphere::new(Ray::new(Point3::new(0.0, -1000.0, 0.0), Vec3::init_zero()), 1000.0, ground)));
+
+    // Same blue glass in three sizes: Beer-Lambert absorption darkens the bigger balls more,
+    // and each ball more through its middle than near the rim
+    let blue_glass = Arc::new(Dielectric::new(1.5).with_transmittance(Color::new(0.2, 0.4, 0.9), 1.0));
+    for (x, radius) in [(-3.2, 0.5), (-1.2, 1.0), (1.8, 1.6)] {
+        world.add(Box::new(Sphere::new(Ray::new(Point3::new(x, radius, 0.0), Vec3::init_zero()), radius, blue_glass.clone())));
+    }
+
+    // Camera
+    let mut camera = Camera::new();
+
+    camera.aspect_ratio = 16.0 / 9.0;
+    camera.image_width = 400;
+    camera.samples_per_pixel = 100;
+    camera.max_depth = 50;
+
+    camera.vfov = 30.0;
+    camera.look_from = Point3::new(0.0, 3.0, 12.0);
+    camera.look_at = Point3::new(0.0, 0.8, 0.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    camera.background_color = Color::new(0.7, 0.8, 1.0);
+
+    camera.defocus_angle = 0.0; // degrees
+    Scene::new(world, camera)
+}
+
+pub fn perlin_sphere() -> Scene {
+    // World
+    let mut world = HittableList::new();
+
+    let perlin_texture: Arc<dyn Texture> = Arc::new(NoiseTexture::new(4.0));
+    let perlin_surface = Arc::new(Lambertian::from_texture(perlin_texture));
+    let perlin_globe = Sphere::new(Ray::new(Point3::new(0.0, -1000.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 1000.0, perlin_surface.clone());
+    let perlin_sphere = Sphere::new(Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 2.0, perlin_surface.clone());
+    world.add(Box::new(perlin_globe));
+    world.add(Box::new(perlin_sphere));
+
+    
+    // Camera
+    let mut camera = Camera::new();
+
+    camera.aspect_ratio = 16.0 / 9.0;
+    camera.image_width = 400;
+    camera.samples_per_pixel = 100;
+    camera.max_depth = 50;
+
+    camera.vfov = 20.0;
+    camera.look_from = Point3::new(13.0, 2.0, 3.0);
+    camera.look_at = Point3::new(0.0, 0.0, 0.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    camera.defocus_angle = 0.0; // degrees
+
+    camera.background_color = Color::new(0.7, 0.8, 1.0);
+    Scene::new(world, camera)
+
+}
+
+pub fn quads() -> Scene {
+    // World
+    let mut world = HittableList::new();
+
+    let left_red = Arc::new(Lambertian::new(Color::new(1.0, 0.2, 0.2)));
+    let back_green = Arc::new(Lambertian::new(Color::new(0.2, 1.0, 0.2)));
+    let right_blue = Arc::new(Lambertian::new(Color::new(0.2, 0.2, 1.0)));
+    let upper_orange = Arc::new(Lambertian::new(Color::new(1.0, 0.5, 0.0)));
+    let lower_teal = Arc::new(Lambertian::new(Color::new(0.2, 0.8, 0.8)));
+
+    world.add(Box::new(Quad::new(Point3::new(-3.0, -2.0, 5.0), Vec3::new(0.0, 0.0, -4.0), Vec3::new(0.0, 4.0, 0.0), left_red)));
+    world.add(Box::new(Quad::new(Point3::new(-2.0, -2.0, 0.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 0.0), back_green)));
+    world.add(Box::new(Quad::new(Point3::new(3.0, -2.0, 1.0), Vec3::new(0.0, 0.0, 4.0), Vec3::new(0.0, 4.0, 0.0), right_blue)));
+    world.add(Box::new(Quad::new(Point3::new(-2.0, 3.0, 1.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0 , 4.0), upper_orange)));
+    world.add(Box::new(Quad::new(Point3::new(-2.0, -3.0, 5.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0 , -4.0), lower_teal)));
+
+    
+    // Camera
+    let mut camera = Camera::new();
+
+    camera.aspect_ratio = 1.0;
+    camera.image_width = 600;
+    camera.samples_per_pixel = 200;
+    camera.max_depth = 50;
+
+    camera.vfov = 100.0;
+    camera.look_from = Point3::new(0.0, 0.0, 9.0);
+    camera.look_at = Point3::new(0.0, 0.0, -1.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    camera.background_color = Color::new(0.7, 0.8, 1.0);
+
+    camera.defocus_angle = 0.0; // degrees
+    Scene::new(world, camera)
+
+}
+
+pub fn simple_light() -> Scene {
+    // World
+    let mut world = HittableList::new();
+
+    let perlin_texture: Arc<dyn Texture> = Arc::new(NoiseTexture::new(4.0));
+    let perlin_surface = Arc::new(Lambertian::from_texture(perlin_texture));
+    let perlin_globe = Sphere::new(Ray::new(Point3::new(0.0, -1000.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 1000.0, perlin_surface.clone());
+    let perlin_sphere = Sphere::new(Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 2.0, perlin_surface.clone());
+    world.add(Box::new(perlin_globe));
+    world.add(Box::new(perlin_sphere));
+
+    let mut lights = HittableList::new();
+    let difflight = Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
+    let quad_light: HittablePtr = Arc::new(Quad::new(Point3::new(3.0, 1.0, -2.0), Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), difflight.clone()));
+    world.add_shared(quad_light.clone());
+    lights.add_shared(quad_light);
+
+    let sphere_light: HittablePtr = Arc::new(Sphere::new(Ray::new(Point3::new(0.0, 7.0, 0.0), Vec3::new(0.0, 0.0, 0.0)), 2.0, difflight.clone()));
+    world.add_shared(sphere_light.clone());
+    lights.add_shared(sphere_light);
+    
+    // Camera
+    let mut camera = Camera::new();
+
+    camera.aspect_ratio = 16.0 / 9.0;
+    camera.image_width = 600;
+    camera.samples_per_pixel = 200;
+    camera.max_depth = 50;
+
+    camera.vfov = 20.0;
+    camera.look_from = Point3::new(26.0, 3.0, 6.0);
+    camera.look_at = Point3::new(0.0, 2.0, 0.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    camera.defocus_angle = 0.0; // degrees
+
+    camera.background_color = Color::new(0.0, 0.0, 0.0);
+    //camera.background_color = Color::new(0.7, 0.8, 1.0);
+    let mut scene = Scene::new(world, camera);
+    scene.lights = lights;
+    scene
+}
+
+
+pub fn cornell_box() -> Scene {
+    // World
+    let mut world = HittableList::new();
+
+    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
+
+    world.add(Box::new(Quad::new(Point3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), Vec3::new(0.0, 555.0, 0.0), green))); // left
+    world.add(Box::new(Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(0.0, 0.0, -555.0), Vec3::new(0.0, 555.0, 0.0), red))); // right
+    world.add(Box::new(Quad::new(Point3::new(0.0, 555.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone()))); // floor
+    world.add(Box::new(Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -555.0), white.clone()))); // floor
+    world.add(Box::new(Quad::new(Point3::new(555.0, 0.0, 555.0), Vec3::new(-555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white.clone()))); // back
+
+    // light
+    let mut lights = HittableList::new();
+    let ceiling_light: HittablePtr = Arc::new(Quad::new(Point3::new(213.0, 554.0, 227.0), Vec3::new(130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 105.0), light));
+    world.add_shared(ceiling_light.clone());
+    lights.add_shared(ceiling_light);
+
+    // box 1
+    let box1 = Quad::make_box(&Point3::new(0.0, 0.0, 0.0), &Point3::new(165.0, 330.0, 165.0), white.clone());
+    let box1 = RotateY::new(Arc::new(box1), 15.0);
+    let box1 = Translate::new(Arc::new(box1), Vec3::new(265.0, 0.0, 295.0));
+
+    // box 2
+    let box2 = Quad::make_box(&Point3::new(0.0, 0.0, 0.0), &Point3::new(165.0, 165.0, 165.0), white.clone());
+    let box2 = RotateY::new(Arc::new(box2), -18.0);
+    let box2 = Translate::new(Arc::new(box2), Vec3::new(130.0, 0.0, 65.0));
+
+    world.add(Box::new(box1));
+    world.add(Box::new(box2));
+
+    // Camera
+    let mut camera = Camera::new();
+
+    camera.aspect_ratio = 1.0;
+    camera.image_width = 600;
+    camera.samples_per_pixel = 200;
+    camera.max_depth = 30;
+
+    camera.vfov = 40.0;
+    camera.look_from = Point3::new(278.0, 278.0, -800.0);
+    camera.look_at = Point3::new(278.0, 278.0, 0.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    camera.defocus_angle = 0.0; // degrees
+
+    camera.background_color = Color::new(0.0, 0.0, 0.0);
+    //camera.background_color = Color::new(0.7, 0.8, 1.0);
+    let mut scene = Scene::new(world, camera);
+    scene.lights = lights;
+    scene
+}
+
+pub fn cornell_smoke() -> Scene {
+    // World
+    let mut world = HittableList::new();
+
+    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::new(Color::new(7.0, 7.0, 7.0)));
+
+    world.add(Box::new(Quad::new(Point3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green))); // left
+    world.add(Box::new(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red))); // right
+    let mut lights = HittableList::new();
+    let ceiling_light: HittablePtr = Arc::new(Quad::new(Point3::new(113.0, 554.0, 127.0), Vec3::new(330.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 305.0), light));
+    world.add_shared(ceiling_light.clone());
+    lights.add_shared(ceiling_light);
+    world.add(Box::new(Quad::new(Point3::new(0.0, 555.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone()))); // floor
+    world.add(Box::new(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white.clone()))); // floor
+    world.add(Box::new(Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white.clone()))); // back
+
+    // add boxes
+    let box1 = Quad::make_box(&Point3::new(0.0, 0.0, 0.0), &Point3::new(165.0, 330.0, 165.0), white.clone());
+    let box1 = RotateY::new(Arc::new(box1), 15.0);
+    let box1 = Translate::new(Arc::new(box1), Vec3::new(265.0, 0.0, 295.0));
+    let smoke_box1 = constant_medium::from_color(Arc::new(box1), 0.01, &Color::new(0.0, 0.0, 0.0));
+
+    let box2 = Quad::make_box(&Point3::new(0.0, 0.0, 0.0), &Point3::new(165.0, 165.0, 165.0), white.clone());
+    let box2 = RotateY::new(Arc::new(box2), -18.0);
+    let box2 = Translate::new(Arc::new(box2), Vec3::new(130.0, 0.0, 65.0));
+    let smoke_box2 = constant_medium::from_color(Arc::new(box2), 0.01, &Color::new(1.0, 1.0, 1.0));
+
+    world.add(Box::new(smoke_box1));
+    world.add(Box::new(smoke_box2));
+
+    // Camera
+    let mut camera = Camera::new();
+
+    camera.aspect_ratio = 1.0;
+    camera.image_width = 600;//600;
+    camera.samples_per_pixel = 200;//200;
+    camera.max_depth = 50;
+
+    camera.vfov = 40.0;
+    camera.look_from = Point3::new(278.0, 278.0, -800.0);
+    camera.look_at = Point3::new(278.0, 278.0, 0.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    camera.defocus_angle = 0.0; // degrees
+
+    camera.background_color = Color::new(0.0, 0.0, 0.0);
+
+    let mut scene = Scene::new(world, camera);
+    scene.lights = lights;
+    scene
+}
+
+
+pub fn final_scene(image_width: u32, samples_per_pixel: u32, max_depth: u32) -> Scene {
+    //let mut boxes1 = HittableList::new();
+
+    let mut world = HittableList::new();
+
+    let ground = Arc::new(Lambertian::new(Color::new(0.48, 0.83, 0.53)));
+
+    let boxes_per_side = 20;
+    for i in 0..boxes_per_side {
+        for j in 0..boxes_per_side{
+            let w = 100.0;
+            let x0 = -1000.0 + i as f64 * w;
+            let z0 = -1000.0 + j as f64 * w;
+            let y0 = 0.0;
+            let x1 = x0 + w;
+            let y1 = random_f64_range(1.0, 101.0);
+            let z1 = z0 + w;
+
+            world.add(Box::new(Quad::make_box(&Point3::new(x0, y0, z0), &Point3::new(x1, y1, z1), ground.clone())));
+        }
+    }
+
+
+    let light = Arc::new(DiffuseLight::new(Color::new(7.0, 7.0, 7.0)));
+    let mut lights = HittableList::new();
+    let ceiling_light: HittablePtr = Arc::new(Quad::new(Point3::new(123.0, 554.0, 147.0), Vec3::new(300.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 265.0), light.clone()));
+    world.add_shared(ceiling_light.clone());
+    lights.add_shared(ceiling_light);
+
+    // Moving sphere
+    let center = Point3::new(400.0, 400.0, 200.0);
+    let direction = Vec3::new(30.0, 0.0, 0.0);
+    let moving_sphere_material = Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.1)));
+    world.add(Box::new(Sphere::new(Ray::new(center, direction), 50.0, moving_sphere_material.clone())));
+
+    // dielectric (glass) sphere
+    let pos1 = Ray::new(Point3::new(260.0, 150.0, 45.0), Vec3::init_zero());
+    world.add(Box::new(Sphere::new(pos1, 50.0, Arc::new(Dielectric::new(1.5)))));
+
+    // metal sphere
+    let pos2 = Ray::new(Point3::new(0.0, 150.0, 145.0), Vec3::init_zero());
+    let albedo = Color::new(0.8, 0.8, 0.9);
+    let fuzz = 0.8;
+    let sphere_material = Arc::new(Metal::new(albedo, fuzz));
+    world.add(Box::new(Sphere::new(pos2, 50.0, sphere_material)));
+
+    
+    // blue glass ball with smoke inside
+    let boundary = Sphere::new(Ray::new(Point3::new(360.0, 150.0, 145.0), Vec3::init_zero()), 70.0, Arc::new(Dielectric::new(1.5)));
+    world.add(Box::new(boundary.clone()));
+    let medium1 = constant_medium::from_color(Arc::new(boundary.clone()), 0.2, &Color::new(0.2, 0.4, 0.9));
+    world.add(Box::new(medium1));
+
+
+    // large sphere with low-density smoke cowering entire scene, creating a foggy atmosphere
+    let boundary2 = Sphere::new(Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::init_zero()), 5000.0, Arc::new(Dielectric::new(1.5)));
+    let medium2 = constant_medium::from_color(Arc::new(boundary2.clone()), 0.0001, &Color::new(1.0, 1.0, 1.0));
+    world.add(Box::new(medium2));  
+
+    // Earth sphere with texture mapping
+    let earth_texture: Arc<dyn Texture> = Arc::new(ImageTexture::new("textures/earthmap.jpg")
+        .with_filter(TextureFilter::Bilinear)
+        .with_wrap(WrapMode::Repeat, WrapMode::Clamp)); // wraps around at the date line
+    let earth_material = Arc::new(Lambertian::from_texture(earth_texture));
+    world.add(Box::new(Sphere::new(Ray::new(Point3::new(400.0, 200.0, 400.0), Vec3::init_zero()), 100.0, earth_material.clone())));
+    
+    // Perlin noise textured sphere
+    // this might be slowing down the rendering significantly, especially at higher resolutions and sample counts
+    let pertex = Arc::new(NoiseTexture::new(0.2));
+    world.add(Box::new(Sphere::new(Ray::new(Point3::new(220.0, 280.0, 300.0), Vec3::init_zero()), 80.0, Arc::new(Lambertian::from_texture(pertex)))));
+
+    // Cluster of small spheres
+    let mut boxes2 = HittableList::new();
+
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+
+    let ns = 1000;
+    for _ in 0..ns {
+        let random_position = Point3::new(
+            random_f64_range(0.0, 165.0),
+            random_f64_range(0.0, 165.0),
+            random_f64_range(0.0, 165.0),
+        );
+        boxes2.add(Box::new(Sphere::new(
+            Ray::new(random_position, Vec3::init_zero()),
+            10.0,
+            white.clone(),
+        )));
+    }
+
+    // Build a BVH for the cluster BEFORE rotating/translating it. The scene BVH only sees the
+    // transformed cluster as one object, so this is where SAH reaches the spheres.
+    let (boxes2_bvh, stats) = LinearBVH::build(&boxes2, SplitMethod::SAH);
+    eprintln!("Cluster BVH: {}", stats);
+    let boxes2_bvh = Arc::new(boxes2_bvh);
+
+    world.add(Box::new(
+        Translate::new(
+            Arc::new(RotateY::new(boxes2_bvh, 15.0)),
+            Vec3::new(-100.0, 270.0, 395.0),
+        )
+    ));
+
+    // Camera
+    let mut camera = Camera::new();
+
+    camera.aspect_ratio = 1.0;
+    camera.image_width = image_width;
+    camera.samples_per_pixel = samples_per_pixel;
+    camera.max_depth = max_depth;
+
+    camera.vfov = 40.0;
+    camera.look_from = Point3::new(478.0, 278.0, -600.0);
+    camera.look_at = Point3::new(278.0, 278.0, 0.0);
+    camera.vup = Vec3::new(0.0, 1.0, 0.0);
+
+    camera.background_color = Color::new(0.0, 0.0, 0.0);
+
+    camera.defocus_angle = 0.0; // degrees
+
+    let mut scene = Scene::new(world, camera); // the renderer builds a BVH over the entire scene
+    scene.lights = lights;
+    scene
+}
+
+pub type SceneFn = fn() -> Scene;
+
+// Scene registry: (name, description, constructor)
+pub const SCENES: &[(&str, &str, SceneFn)] = &[
+    ("bouncing_spheres", "Book 1 cover: random spheres, motion blur and depth of field", bouncing_spheres),
+    ("checkered_sphere", "Two spheres with a solid checker texture", checkered_sphere),
+    ("earth", "Image-textured globe", earth),
+    ("tinted_glass", "Blue glass balls absorbing more the thicker they are", tinted_glass),
+    ("perlin_sphere", "Perlin marble texture on a sphere and ground", perlin_sphere),
+    ("quads", "Five colored quads", quads),
+    ("simple_light", "Perlin spheres lit by a quad and a sphere light", simple_light),
+    ("cornell_box", "Cornell box with two rotated boxes", cornell_box),
+    ("cornell_smoke", "Cornell box with smoke and fog boxes", cornell_smoke),
+    ("final_scene", "Book 2 final scene at full quality (800px, 10000 spp, up to 40 bounces; over an hour)", final_scene_full),
+    ("final_scene_preview", "Book 2 final scene preview (400px, 500 spp, up to 10 bounces; under a minute)", final_scene_preview),
+];
+
+pub const DEFAULT_SCENE: &str = "final_scene_preview";
+
+pub fn find(name: &str) -> Option<SceneFn> {
+    SCENES.iter().find(|(n, _, _)| *n == name).map(|(_, _, f)| *f)
+}
+
+fn final_scene_full() -> Scene {
+    final_scene(800, 10000, 40)
+}
+
+fn final_scene_preview() -> Scene {
+    final_scene(400, 500, 10)
+}
@@ -0,0 +1,165 @@
+// spectrum.rs
+// Single-wavelength paths for dispersion. A path that hits a dispersive surface picks one
+// wavelength and carries it from then on; its throughput is weighted by that wavelength's RGB
+// color, so averaging many paths converges back to the RGB result apart from the spectral
+// separation.
+
+use once_cell::sync::Lazy;
+
+use crate::utils::prelude::random_f64;
+use crate::vec3::Color;
+
+// Visible range in nm, sampled uniformly
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+// Average of wavelength_to_rgb over the visible range, per channel
+static RGB_MEAN: Lazy<Color> = Lazy::new(|| {
+    let steps = 4000;
+    let mut sum = Color::init_zero();
+    for i in 0..steps {
+        let lambda = LAMBDA_MIN + (i as f64 + 0.5) * (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+        sum = sum + wavelength_to_rgb(lambda);
+    }
+    sum / steps as f64
+});
+
+// Piecewise Gaussian with different widths on either side of the peak
+#[inline]
+fn lobe(lambda: f64, mu: f64, sigma_below: f64, sigma_above: f64) -> f64 {
+    let t = (lambda - mu) / if lambda < mu { sigma_below } else { sigma_above };
+    (-0.5 * t * t).exp()
+}
+
+// CIE 1931 color matching functions, multi-lobe fit from Wyman, Sloan and Shirley 2013
+pub fn wavelength_to_xyz(lambda: f64) -> Color {
+    let x = 1.056 * lobe(lambda, 599.8, 37.9, 31.0) + 0.362 * lobe(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * lobe(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(lambda, 568.8, 46.9, 40.5) + 0.286 * lobe(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(lambda, 437.0, 11.8, 36.0) + 0.681 * lobe(lambda, 459.0, 26.0, 13.8);
+    Color::new(x, y, z)
+}
+
+// Linear sRGB of a single wavelength. Saturated wavelengths fall outside the gamut and get
+// negative channels; they're kept so averages stay right.
+pub fn wavelength_to_rgb(lambda: f64) -> Color {
+    let c = wavelength_to_xyz(lambda);
+    let (x, y, z) = (c.x(), c.y(), c.z());
+    Color::new(
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    )
+}
+
+// Pick a wavelength for a path, and the weight its throughput gets: the wavelength's color
+// over the pdf, normalized so a flat spectrum averages to white
+pub fn sample_wavelength() -> (f64, Color) {
+    let lambda = LAMBDA_MIN + random_f64() * (LAMBDA_MAX - LAMBDA_MIN);
+    let rgb = wavelength_to_rgb(lambda);
+    let mean = *RGB_MEAN;
+    (lambda, Color::new(rgb.x() / mean.x(), rgb.y() / mean.y(), rgb.z() / mean.z()))
+}
+
+// Wavelength-dependent index of refraction
+#[derive(Copy, Clone, Debug)]
+pub enum Ior {
+    Constant(f64),
+    // n = a + b / lambda^2, lambda in micrometers
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum b_i lambda^2 / (lambda^2 - c_i), lambda in micrometers
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Ior {
+    // Index at a wavelength in nm
+    pub fn at(&self, lambda: f64) -> f64 {
+        let l2 = (lambda * 1e-3).powi(2);
+        match *self {
+            Ior::Constant(n) => n,
+            Ior::Cauchy { a, b } => a + b / l2,
+            Ior::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>();
+                n2.max(1.0).sqrt()
+            }
+        }
+    }
+
+    // Index at the sodium D line, what a single refraction index usually means
+    pub fn nominal(&self) -> f64 {
+        self.at(589.3)
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Ior::Constant(_))
+    }
+
+    // Common optical glass
+    pub fn bk7() -> Self {
+        Ior::Sellmeier { b: [1.03961212, 0.231792344, 1.01046945], c: [0.00600069867, 0.0200179144, 103.560653] }
+    }
+
+    // Dense flint glass, strongly dispersive: good for prisms
+    pub fn sf11() -> Self {
+        Ior::Sellmeier { b: [1.73759695, 0.313747346, 1.89878101], c: [0.013188707, 0.0623068142, 155.23629] }
+    }
+
+    pub fn fused_silica() -> Self {
+        Ior::Sellmeier { b: [0.6961663, 0.4079426, 0.8974794], c: [0.0046791482, 0.0135120631, 97.93400254] }
+    }
+
+    pub fn diamond() -> Self {
+        Ior::Sellmeier { b: [0.3306, 4.3356, 0.0], c: [0.030625, 0.011236, 0.0] }
+    }
+
+    pub fn water() -> Self {
+        Ior::Cauchy { a: 1.3199, b: 0.00305 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::microfacet;
+
+    const SAMPLES: usize = 200_000;
+
+    // Mean path weight over sampled wavelengths, each path's value scaled by `f(lambda)`
+    fn spectral_estimate(f: impl Fn(f64) -> f64) -> Color {
+        let mut sum = Color::init_zero();
+        for _ in 0..SAMPLES {
+            let (lambda, weight) = sample_wavelength();
+            sum = sum + weight * f(lambda);
+        }
+        sum / SAMPLES as f64
+    }
+
+    #[test]
+    fn white_spectrum_integrates_to_white() {
+        let white = spectral_estimate(|_| 1.0);
+        for c in 0..3 {
+            assert!((white[c] - 1.0).abs() < 0.02, "flat spectrum gives {:?}", white);
+        }
+
+        // The CIE matching functions enclose (nearly) the same area, about 106.9 nm each
+        let steps = 4000;
+        let dl = (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+        let area = (0..steps).fold(Color::init_zero(), |sum, i| sum + wavelength_to_xyz(LAMBDA_MIN + (i as f64 + 0.5) * dl) * dl);
+        for c in 0..3 {
+            assert!((area[c] - 106.9).abs() < 0.02 * 106.9, "matching function areas {:?}", area);
+        }
+    }
+
+    #[test]
+    fn constant_ior_matches_rgb() {
+        // A path through non-dispersive glass that carries a wavelength anyway must converge to
+        // what an RGB path gets, here the Fresnel reflectance at 60 degrees
+        let ior = Ior::Constant(1.5);
+        let cos_theta = 0.5;
+        let rgb = microfacet::fresnel_dielectric(cos_theta, ior.nominal());
+        let spectral = spectral_estimate(|lambda| microfacet::fresnel_dielectric(cos_theta, ior.at(lambda)));
+        for c in 0..3 {
+            assert!((spectral[c] - rgb).abs() < 0.02 * rgb, "spectral {:?} vs RGB {}", spectral, rgb);
+        }
+    }
+}
@@ -6,6 +6,8 @@ use crate::interval::Interval;
 use crate::material::Material;
 use std::sync::Arc;
 use crate::aabb::AABB;
+use crate::onb::ONB;
+use crate::utils::prelude::{random_f64, PI};
 
 
 #[derive(Clone)]
@@ -42,6 +44,19 @@ impl Sphere {
         &self.material
     }
 
+    // Direction inside the cone subtended by a sphere at the given squared distance, around +z
+    fn random_to_sphere(radius: f64, distance_squared: f64) -> Vec3 {
+        let r1 = random_f64();
+        let r2 = random_f64();
+        let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).sqrt() - 1.0);
+
+        let phi = 2.0 * PI * r1;
+        let x = phi.cos() * (1.0 - z * z).sqrt();
+        let y = phi.sin() * (1.0 - z * z).sqrt();
+
+        Vec3::new(x, y, z)
+    }
+
     #[inline]
     fn get_sphere_uv(p: &Point3) -> (f64, f64) {
         let theta = (-p.y()).acos();
@@ -85,7 +100,9 @@ impl Hittable for Sphere {
         let (u, v) = Sphere::get_sphere_uv(&outward_normal);
         rec.u = u;
         rec.v = v;
-        rec.material = Some(self.material.as_ref());
+        rec.set_material(self.material.as_ref());
+        // u grows with the angle around y; zero at the poles
+        rec.dpdu = Some(Vec3::new(outward_normal.z(), 0.0, -outward_normal.x()));
 
         return true;
     }
@@ -93,4 +110,75 @@ impl Hittable for Sphere {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    // Uniform over the cone of directions that see the sphere. Moving spheres are sampled
+    // at their time-0 position.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f64 {
+        let mut rec = HitRecord::new();
+        if !self.hit(&Ray::new(origin, direction), Interval::new(0.001, f64::INFINITY), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = (self.center.origin() - origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            // Inside: directions are picked uniformly over the whole sphere
+            return 1.0 / (4.0 * PI);
+        }
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let direction = self.center.origin() - origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return Vec3::random_unit_vector();
+        }
+        let uvw = ONB::new(direction);
+        uvw.transform(Sphere::random_to_sphere(self.radius, distance_squared))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::ray::point_to_ray;
+    use crate::vec3::Color;
+
+    const SAMPLES: usize = 1_000_000;
+
+    fn sphere() -> Sphere {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        Sphere::new(point_to_ray(Point3::new(0.5, 2.0, -0.3)), 1.2, material)
+    }
+
+    // Monte Carlo over uniform directions: the mean of pdf / (1 / 4 pi) is the integral
+    fn pdf_integral(sphere: &Sphere, origin: Point3) -> f64 {
+        let sum: f64 = (0..SAMPLES).map(|_| sphere.pdf_value(origin, Vec3::random_unit_vector())).sum();
+        sum * 4.0 * PI / SAMPLES as f64
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let sphere = sphere();
+        for origin in [Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 0.5, 1.0), Point3::new(0.7, 2.4, 0.0)] {
+            let integral = pdf_integral(&sphere, origin);
+            assert!((integral - 1.0).abs() < 0.02, "pdf integrates to {} from {:?}", integral, origin);
+        }
+    }
+
+    #[test]
+    fn random_matches_pdf() {
+        // Samples must all hit, with the constant pdf of the cone they are drawn from
+        let sphere = sphere();
+        for origin in [Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 0.5, 1.0), Point3::new(0.7, 2.4, 0.0)] {
+            let expected = sphere.pdf_value(origin, sphere.center.origin() - origin);
+            for _ in 0..10_000 {
+                let pdf = sphere.pdf_value(origin, sphere.random(origin));
+                assert!((pdf - expected).abs() < 1e-9 * expected, "pdf {} vs {} from {:?}", pdf, expected, origin);
+            }
+        }
+    }
 }
@@ -9,6 +9,13 @@ use crate::perlin::Perlin;
 
 pub trait Texture : Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Point3) -> Color;  
+
+    // Single-channel lookup (roughness maps and the like): the mean of the three channels. The
+    // values must be linear, so image maps used this way are loaded with ImageTexture::as_data
+    fn scalar(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        let c = self.value(u, v, p);
+        (c.x() + c.y() + c.z()) / 3.0
+    }
 }
 
 // Solid color texture
@@ -75,34 +82,172 @@ impl Texture for CheckerTexture{
 
 // Image texture
 
+// How texels are blended for a lookup
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextureFilter {
+    Nearest,
+    Bilinear,
+    Bicubic, // Catmull-Rom over the 4x4 texels around the lookup
+}
+
+// What a texture coordinate outside [0,1] reads, per axis
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat, // tile
+    Clamp,  // stretch the edge texels
+    Mirror, // tile, flipping every other copy so the seams match
+    Black,  // nothing outside the image
+}
+
+impl WrapMode {
+    // Texel index `i` on an axis of `n` texels, or None if it falls off a black border
+    #[inline]
+    fn wrap(self, i: i64, n: i64) -> Option<i64> {
+        match self {
+            WrapMode::Repeat => Some(i.rem_euclid(n)),
+            WrapMode::Clamp => Some(i.clamp(0, n - 1)),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                Some(if m < n { m } else { 2 * n - 1 - m })
+            }
+            WrapMode::Black => (0..n).contains(&i).then_some(i),
+        }
+    }
+}
+
 pub struct ImageTexture{
     data: ImageTextureData,
+    filter: TextureFilter,
+    wrap_u: WrapMode,
+    wrap_v: WrapMode,
+    // UV transform: scale, then rotate about the middle of the image, then offset
+    scale: (f64, f64),
+    offset: (f64, f64),
+    sin_theta: f64,
+    cos_theta: f64,
+    srgb: bool, // color images are sRGB-encoded, data maps are not
 }
 
 impl ImageTexture{
     pub fn new(filename: &str) -> Self {
-        let data = ImageTextureData::load_rgb8(filename).expect("Failed to load image");
-        ImageTexture { data }
+        Self::load(filename).expect("Failed to load image")
+    }
+
+    // Fallible version of new() for callers that want to report a missing or broken file
+    pub fn load(filename: &str) -> Result<Self, image::ImageError> {
+        Ok(Self::from_data(ImageTextureData::load_rgb8(filename)?))
+    }
+
+    // An image already in memory
+    pub fn from_data(data: ImageTextureData) -> Self {
+        ImageTexture {
+            data,
+            filter: TextureFilter::Nearest,
+            wrap_u: WrapMode::Clamp,
+            wrap_v: WrapMode::Clamp,
+            scale: (1.0, 1.0),
+            offset: (0.0, 0.0),
+            sin_theta: 0.0,
+            cos_theta: 1.0,
+            srgb: true,
+        }
+    }
+
+    // Read the bytes as linear values rather than sRGB color, for roughness, metalness and mask
+    // maps
+    pub fn as_data(mut self) -> Self {
+        self.srgb = false;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: TextureFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap_u: WrapMode, wrap_v: WrapMode) -> Self {
+        self.wrap_u = wrap_u;
+        self.wrap_v = wrap_v;
+        self
+    }
+
+    // Scale of 4 tiles the image 4 times across; rotation is in degrees
+    pub fn with_transform(mut self, scale: (f64, f64), offset: (f64, f64), rotation: f64) -> Self {
+        let radians = rotation.to_radians();
+        self.scale = scale;
+        self.offset = offset;
+        self.sin_theta = radians.sin();
+        self.cos_theta = radians.cos();
+        self
+    }
+
+    #[inline]
+    fn texel(&self, i: i64, j: i64) -> Color {
+        let (w, h) = (self.data.width as i64, self.data.height as i64);
+        match (self.wrap_u.wrap(i, w), self.wrap_v.wrap(j, h)) {
+            (Some(i), Some(j)) if self.srgb => ImageTextureData::pixel_data(&self.data, i as u32, j as u32),
+            (Some(i), Some(j)) => ImageTextureData::raw_pixel_data(&self.data, i as u32, j as u32),
+            _ => Color::init_zero(),
+        }
     }
 }
 
+// Catmull-Rom weights for the four texels around a lookup `t` past the second one
+#[inline]
+fn catmull_rom(t: f64) -> [f64; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
+}
+
 impl Texture for ImageTexture{
 
     fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
-        if self.data.width <= 0 || self.data.height <= 0 {
+        if self.data.width == 0 || self.data.height == 0 {
             return Color::new(0.0, 1.0, 1.0); // cyan for missing texture
         }
 
-        // clamp input texture coordinates to [0,1]x[1,0]
-        let u = u.clamp(0.0, 1.0);
-        let v = 1.0 - v.clamp(0.0, 1.0); // flip V to image coordinates
+        let su = (u - 0.5) * self.scale.0;
+        let sv = (v - 0.5) * self.scale.1;
+        let u = self.cos_theta * su - self.sin_theta * sv + 0.5 + self.offset.0;
+        let v = self.sin_theta * su + self.cos_theta * sv + 0.5 + self.offset.1;
+
+        // Continuous texel coordinates, V flipped to image rows; texel centers are at +0.5
+        let x = u * self.data.width as f64;
+        let y = (1.0 - v) * self.data.height as f64;
 
-        let i = (u * self.data.width as f64) as u32;
-        let j = (v * self.data.height as f64) as u32;
-        let pixel = ImageTextureData::pixel_data( &self.data, i, j);
-        
-        //let color_scale = 1.0 / 255.0;
-        Color::new(pixel.x(), pixel.y(), pixel.z())
+        match self.filter {
+            TextureFilter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            TextureFilter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (i, j) = (x.floor() as i64, y.floor() as i64);
+                let (fx, fy) = (x - x.floor(), y - y.floor());
+                let top = self.texel(i, j) * (1.0 - fx) + self.texel(i + 1, j) * fx;
+                let bottom = self.texel(i, j + 1) * (1.0 - fx) + self.texel(i + 1, j + 1) * fx;
+                top * (1.0 - fy) + bottom * fy
+            }
+            TextureFilter::Bicubic => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (i, j) = (x.floor() as i64, y.floor() as i64);
+                let wx = catmull_rom(x - x.floor());
+                let wy = catmull_rom(y - y.floor());
+                let mut color = Color::init_zero();
+                for (dj, wy) in wy.iter().enumerate() {
+                    let mut row = Color::init_zero();
+                    for (di, wx) in wx.iter().enumerate() {
+                        row = row + self.texel(i + di as i64 - 1, j + dj as i64 - 1) * *wx;
+                    }
+                    color = color + row * *wy;
+                }
+                // Catmull-Rom overshoots a little at hard edges; no negative light
+                Color::new(color.x().max(0.0), color.y().max(0.0), color.z().max(0.0))
+            }
+        }
     }
 }
 
@@ -131,3 +276,63 @@ impl Texture for NoiseTexture{
 
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A width x height data image whose texel (i, j) holds (i, j, 1) / 255
+    fn image(width: u32, height: u32) -> ImageTexture {
+        let mut rgb = Vec::new();
+        for j in 0..height {
+            for i in 0..width {
+                rgb.extend_from_slice(&[i as u8, j as u8, 1]);
+            }
+        }
+        ImageTexture::from_data(ImageTextureData { width, height, rgb }).as_data()
+    }
+
+    fn texel(i: u32, j: u32) -> Color {
+        Color::new(i as f64 / 255.0, j as f64 / 255.0, 1.0 / 255.0)
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let n = 4;
+        let wrap = |mode: WrapMode| [-1, n, 2 * n].map(|i| mode.wrap(i, n));
+        assert_eq!(wrap(WrapMode::Repeat), [Some(3), Some(0), Some(0)]);
+        assert_eq!(wrap(WrapMode::Clamp), [Some(0), Some(3), Some(3)]);
+        assert_eq!(wrap(WrapMode::Mirror), [Some(0), Some(3), Some(0)]);
+        assert_eq!(wrap(WrapMode::Black), [None, None, None]);
+    }
+
+    #[test]
+    fn filters_at_texel_center_are_exact() {
+        // Both filters interpolate, so they return the texel itself at its center
+        let (w, h) = (4, 2);
+        for filter in [TextureFilter::Bilinear, TextureFilter::Bicubic] {
+            let texture = image(w, h).with_filter(filter).with_wrap(WrapMode::Repeat, WrapMode::Repeat);
+            for j in 0..h {
+                for i in 0..w {
+                    let u = (i as f64 + 0.5) / w as f64;
+                    let v = 1.0 - (j as f64 + 0.5) / h as f64;
+                    assert_eq!(texture.value(u, v, &Point3::init_zero()), texel(i, j), "{:?} at ({}, {})", filter, i, j);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotation_moves_corners() {
+        let texture = image(2, 2).with_transform((1.0, 1.0), (0.0, 0.0), 90.0);
+        let p = Point3::init_zero();
+
+        // Unrotated, the top right of UV space is the top right texel
+        assert_eq!(image(2, 2).value(0.9, 0.9, &p), texel(1, 0));
+        // Turned a quarter counterclockwise, it reads the top left texel, and so on around
+        assert_eq!(texture.value(0.9, 0.9, &p), texel(0, 0));
+        assert_eq!(texture.value(0.9, 0.1, &p), texel(1, 0));
+        assert_eq!(texture.value(0.1, 0.1, &p), texel(1, 1));
+        assert_eq!(texture.value(0.1, 0.9, &p), texel(0, 1));
+    }
+}
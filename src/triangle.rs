@@ -0,0 +1,364 @@
+// src/triangle.rs
+
+use std::ops::Range;
+use std::sync::Arc;
+use crate::vec3::{Point3, Vec3};
+use crate::material::Material;
+use crate::aabb::AABB;
+use crate::bvh::SplitMethod;
+use crate::linear_bvh::FlatTree;
+use crate::hittable::{Hittable, HitRecord};
+use crate::interval::Interval;
+use crate::ray::Ray;
+
+// Unit normal and the plane-coordinate vector w of a triangle a, a+u, a+v, set up the same
+// way as Quad. Everything else the intersection needs comes from the corners.
+#[derive(Copy, Clone, Debug)]
+struct Face {
+    normal: Vec3,
+    w: Vec3,
+}
+
+impl Face {
+    #[inline]
+    fn new(u: Vec3, v: Vec3) -> Self {
+        let n = u.cross(v);
+        Self { normal: n.unit_vector(), w: n / n.dot(n) }
+    }
+
+    // Returns (t, alpha, beta) where the hit point is a + alpha*u + beta*v
+    #[inline]
+    fn intersect(&self, a: Point3, u: Vec3, v: Vec3, r: &Ray, ray_t: Interval) -> Option<(f64, f64, f64)> {
+        let denom = self.normal.dot(r.direction());
+
+        // Parallel to the plane (or a degenerate triangle, where the normal is NaN)
+        if denom.abs() < 1e-8 || denom.is_nan() {
+            return None;
+        }
+
+        let t = self.normal.dot(a - r.origin()) / denom;
+        if !ray_t.contains(t) {
+            return None;
+        }
+
+        let planar_hitpt_vector = r.at(t) - a;
+        let alpha = self.w.dot(planar_hitpt_vector.cross(v));
+        let beta = self.w.dot(u.cross(planar_hitpt_vector));
+
+        // Inside the triangle when both coordinates are positive and sum to at most one
+        if alpha < 0.0 || beta < 0.0 || alpha + beta > 1.0 {
+            return None;
+        }
+
+        Some((t, alpha, beta))
+    }
+}
+
+fn triangle_box(a: Point3, b: Point3, c: Point3) -> AABB {
+    AABB::from_two_boxes(AABB::extrema_box(a, b), AABB::extrema_box(a, c))
+}
+
+// Optional per-vertex attributes of one triangle
+#[derive(Copy, Clone, Default)]
+struct VertexAttributes {
+    normals: Option<[Vec3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+}
+
+// Fill in the hit record; weights are the barycentric weights of vertices a, b, c
+#[inline]
+fn set_hit_record<'a>(
+    rec: &mut HitRecord<'a>,
+    r: &Ray,
+    t: f64,
+    geometric_normal: Vec3,
+    weights: [f64; 3],
+    attributes: VertexAttributes,
+    material: &'a dyn Material,
+) {
+    rec.t = t;
+    rec.p = r.at(t);
+    rec.set_material(material);
+    rec.set_face_normal(r, geometric_normal);
+
+    // Smooth shading: interpolate the vertex normals, keeping them on the side the ray came from
+    if let Some(n) = attributes.normals {
+        let shading_normal = (n[0] * weights[0] + n[1] * weights[1] + n[2] * weights[2]).unit_vector();
+        rec.normal = if rec.front_face { shading_normal } else { -shading_normal };
+    }
+
+    match attributes.uvs {
+        Some(uv) => {
+            rec.u = uv[0].0 * weights[0] + uv[1].0 * weights[1] + uv[2].0 * weights[2];
+            rec.v = uv[0].1 * weights[0] + uv[1].1 * weights[1] + uv[2].1 * weights[2];
+        }
+        None => {
+            // Same convention as Quad: the plane coordinates along the two edges
+            rec.u = weights[1];
+            rec.v = weights[2];
+        }
+    }
+}
+
+// Direction of increasing u across a triangle with edges e1 = b - a and e2 = c - a: solve
+// e1 = dpdu du1 + dpdv dv1, e2 = dpdu du2 + dpdv dv2. Without UVs, or with UVs that collapse
+// the triangle, u runs along e1 as in set_hit_record
+#[inline]
+fn triangle_dpdu(e1: Vec3, e2: Vec3, uvs: Option<[(f64, f64); 3]>) -> Vec3 {
+    let Some(uv) = uvs else {
+        return e1;
+    };
+    let (du1, dv1) = (uv[1].0 - uv[0].0, uv[1].1 - uv[0].1);
+    let (du2, dv2) = (uv[2].0 - uv[0].0, uv[2].1 - uv[0].1);
+    let det = du1 * dv2 - dv1 * du2;
+    if det.abs() > 1e-12 { (e1 * dv2 - e2 * dv1) / det } else { e1 }
+}
+
+
+// Single triangle with optional per-vertex normals and texture coordinates
+pub struct Triangle {
+    a: Point3,
+    u: Vec3,
+    v: Vec3,
+    face: Face,
+    attributes: VertexAttributes,
+    material: Arc<dyn Material>,
+    bbox: AABB,
+}
+
+impl Triangle {
+    pub fn new(a: Point3, b: Point3, c: Point3, material: Arc<dyn Material>) -> Self {
+        let (u, v) = (b - a, c - a);
+        let bbox = triangle_box(a, b, c);
+        Self { a, u, v, face: Face::new(u, v), attributes: VertexAttributes::default(), material, bbox }
+    }
+
+    pub fn with_normals(mut self, na: Vec3, nb: Vec3, nc: Vec3) -> Self {
+        self.attributes.normals = Some([na.unit_vector(), nb.unit_vector(), nc.unit_vector()]);
+        self
+    }
+
+    pub fn with_uvs(mut self, uva: (f64, f64), uvb: (f64, f64), uvc: (f64, f64)) -> Self {
+        self.attributes.uvs = Some([uva, uvb, uvc]);
+        self
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.face.normal
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let Some((t, alpha, beta)) = self.face.intersect(self.a, self.u, self.v, r, ray_t) else {
+            return false;
+        };
+
+        let weights = [1.0 - alpha - beta, alpha, beta];
+        set_hit_record(rec, r, t, self.face.normal, weights, self.attributes, self.material.as_ref());
+        rec.dpdu = Some(triangle_dpdu(self.u, self.v, self.attributes.uvs));
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+
+// Indexed triangle mesh. Vertex positions (and the optional normals and UVs, one per vertex)
+// are stored once and shared by all triangles; each triangle is three indices into them plus
+// its cached normal and w. The mesh's own BVH is built over the triangles, which are stored
+// in leaf order, so there is no per-triangle object.
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    indices: Vec<[u32; 3]>,
+    faces: Vec<Face>,
+    material: Arc<dyn Material>,
+    bvh: FlatTree,
+}
+
+impl TriangleMesh {
+    // normals and uvs, when given, must have one entry per position, and every index must
+    // refer to a position
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Option<Vec<Vec3>>,
+        uvs: Option<Vec<(f64, f64)>>,
+        indices: Vec<[u32; 3]>,
+        material: Arc<dyn Material>,
+    ) -> Result<Self, String> {
+        let vertex_count = positions.len();
+        if let Some(bad) = indices.iter().find(|tri| tri.iter().any(|&i| i as usize >= vertex_count)) {
+            return Err(format!("mesh triangle {:?} refers to a vertex past the {} positions", bad, vertex_count));
+        }
+        if normals.as_ref().is_some_and(|n| n.len() != vertex_count) {
+            return Err(format!("mesh needs one normal per position ({})", vertex_count));
+        }
+        if uvs.as_ref().is_some_and(|uv| uv.len() != vertex_count) {
+            return Err(format!("mesh needs one uv per position ({})", vertex_count));
+        }
+
+        let corners = |tri: [u32; 3]| tri.map(|i| positions[i as usize]);
+        let boxes: Vec<AABB> = indices.iter().map(|&tri| {
+            let [a, b, c] = corners(tri);
+            triangle_box(a, b, c)
+        }).collect();
+
+        // Build over triangle numbers, then store the triangles in the order the leaves use
+        let mut order: Vec<u32> = (0..indices.len() as u32).collect();
+        let (bvh, _) = FlatTree::build(&mut order, SplitMethod::SAH, &|&i: &u32| boxes[i as usize]);
+        let indices: Vec<[u32; 3]> = order.iter().map(|&i| indices[i as usize]).collect();
+        let faces = indices.iter().map(|&tri| {
+            let [a, b, c] = corners(tri);
+            Face::new(b - a, c - a)
+        }).collect();
+
+        let normals = normals.map(|n| n.into_iter().map(|v| v.unit_vector()).collect());
+        Ok(Self { positions, normals, uvs, indices, faces, material, bvh })
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    // Closest hit among the triangles in range, as (t, alpha, beta, triangle)
+    #[inline]
+    fn hit_triangles(&self, range: Range<usize>, r: &Ray, mut ray_t: Interval) -> Option<(f64, f64, f64, usize)> {
+        let mut closest = None;
+        for index in range {
+            let [a, b, c] = self.indices[index].map(|i| self.positions[i as usize]);
+            if let Some((t, alpha, beta)) = self.faces[index].intersect(a, b - a, c - a, r, ray_t) {
+                ray_t.max = t;
+                closest = Some((t, alpha, beta, index));
+            }
+        }
+        closest
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit<'a>(&'a self, r: &Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        // Only the closest triangle fills in the record
+        let mut closest = None;
+        self.bvh.hit(r, ray_t, |range, interval| {
+            let found = self.hit_triangles(range, r, interval)?;
+            closest = Some(found);
+            Some(found.0)
+        });
+        let Some((t, alpha, beta, index)) = closest else {
+            return false;
+        };
+
+        let [a, b, c] = self.indices[index].map(|i| i as usize);
+        let attributes = VertexAttributes {
+            normals: self.normals.as_ref().map(|n| [n[a], n[b], n[c]]),
+            uvs: self.uvs.as_ref().map(|uv| [uv[a], uv[b], uv[c]]),
+        };
+        let weights = [1.0 - alpha - beta, alpha, beta];
+        set_hit_record(rec, r, t, self.faces[index].normal, weights, attributes, self.material.as_ref());
+        let (pa, pb, pc) = (self.positions[a], self.positions[b], self.positions[c]);
+        rec.dpdu = Some(triangle_dpdu(pb - pa, pc - pa, attributes.uvs));
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bvh.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable_list::HittableList;
+    use crate::material::Lambertian;
+    use crate::vec3::Color;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn mesh_matches_separate_triangles() {
+        // A bumpy grid, so the mesh BVH has overlapping leaves
+        let n = 12;
+        let mut positions = Vec::new();
+        for j in 0..=n {
+            for i in 0..=n {
+                let (x, z) = (i as f64, j as f64);
+                positions.push(Point3::new(x, (x * 0.7).sin() * (z * 0.5).cos(), z));
+            }
+        }
+        let mut indices = Vec::new();
+        for j in 0..n {
+            for i in 0..n {
+                let k = (j * (n + 1) + i) as u32;
+                let row = n as u32 + 1;
+                indices.push([k, k + 1, k + row]);
+                indices.push([k + 1, k + row + 1, k + row]);
+            }
+        }
+
+        let mut triangles = HittableList::new();
+        for tri in &indices {
+            let [a, b, c] = tri.map(|i| positions[i as usize]);
+            triangles.add(Box::new(Triangle::new(a, b, c, material())));
+        }
+        let mesh = TriangleMesh::new(positions, None, None, indices, material()).unwrap();
+        assert_eq!(mesh.triangle_count(), 2 * n * n);
+
+        for k in 0..500 {
+            let origin = Point3::new((k % 25) as f64 * 0.5 - 0.3, 3.0, (k / 25) as f64 * 0.6 - 0.2);
+            let r = Ray::new(origin, Vec3::new(0.3, -1.0, 0.17 - (k % 7) as f64 * 0.05));
+            let (mut expected, mut rec) = (HitRecord::new(), HitRecord::new());
+            let hit_list = triangles.hit(&r, Interval::new(0.001, f64::INFINITY), &mut expected);
+            let hit_mesh = mesh.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec);
+            assert_eq!(hit_mesh, hit_list);
+            if hit_mesh {
+                assert!((rec.t - expected.t).abs() < 1e-9);
+                assert!((rec.normal - expected.normal).length() < 1e-9);
+                assert!((rec.u - expected.u).abs() < 1e-9 && (rec.v - expected.v).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn bad_mesh_data_is_an_error() {
+        let positions = vec![Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 0.0)];
+
+        let error = TriangleMesh::new(positions.clone(), None, None, vec![[0, 1, 3]], material()).err().unwrap();
+        assert_eq!(error, "mesh triangle [0, 1, 3] refers to a vertex past the 3 positions");
+
+        let normals = Some(vec![Vec3::new(0.0, 0.0, 1.0)]);
+        assert!(TriangleMesh::new(positions.clone(), normals, None, vec![[0, 1, 2]], material()).is_err());
+
+        // An empty mesh is fine and never hit
+        let empty = TriangleMesh::new(positions, None, None, Vec::new(), material()).unwrap();
+        let r = Ray::new(Point3::new(0.2, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!empty.hit(&r, Interval::new(0.001, f64::INFINITY), &mut HitRecord::new()));
+    }
+
+    #[test]
+    fn dpdu_follows_the_uvs() {
+        // u runs down the triangle's y edge, so the tangent must too, in the mesh as well
+        let (a, b, c) = (Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 0.0, 0.0), Point3::new(0.0, 2.0, 0.0));
+        let uvs = [(1.0, 0.0), (1.0, 1.0), (0.0, 0.0)];
+        let triangle = Triangle::new(a, b, c, material()).with_uvs(uvs[0], uvs[1], uvs[2]);
+        let mesh = TriangleMesh::new(vec![a, b, c], None, Some(uvs.to_vec()), vec![[0, 1, 2]], material()).unwrap();
+        let plain = Triangle::new(a, b, c, material());
+
+        let r = Ray::new(Point3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        for (object, expected) in [(&triangle as &dyn Hittable, Vec3::new(0.0, -2.0, 0.0)), (&mesh, Vec3::new(0.0, -2.0, 0.0)), (&plain, b - a)] {
+            let mut rec = HitRecord::new();
+            assert!(object.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+            let dpdu = rec.dpdu.unwrap();
+            assert!((dpdu - expected).length() < 1e-9, "dpdu {:?} != {:?}", dpdu, expected);
+        }
+    }
+}
@@ -12,27 +12,36 @@ pub mod prelude {
     pub use crate::ray::{Ray};
 
     // Hittables
-    pub use crate::hittable::{HitRecord, Hittable};
+    pub use crate::hittable::{HitRecord, Hittable, Frame};
     pub use crate::sphere::Sphere;
-    pub use crate::hittable_list::HittableList;
+    pub use crate::hittable_list::{HittableList, HittablePtr};
     pub use crate::interval::Interval;
     pub use crate::quad::Quad;
+    pub use crate::triangle::{Triangle, TriangleMesh};
+    pub use crate::obj_loader::{load_obj, load_obj_with, ObjOptions};
 
     //BVH
-    pub use crate::bvh::BVHNode;
+    pub use crate::bvh::{BVHNode, SplitMethod};
+    pub use crate::linear_bvh::LinearBVH;
 
     //TRansformations
     pub use crate::hittable::{Translate, RotateY};
 
     // Textures
-    pub use crate::texture::{Texture, SolidColor, CheckerTexture, ImageTexture, NoiseTexture};
+    pub use crate::texture::{Texture, SolidColor, CheckerTexture, ImageTexture, NoiseTexture, TextureFilter, WrapMode};
 
     // Camera
     pub use crate::camera::Camera;
+    pub use crate::framebuffer::FrameBuffer;
+    pub use crate::scene::Scene;
 
     // Materials and mediums
-    pub use crate::material::{Material, Lambertian, Metal, Dielectric, DiffuseLight, Isotropic};
+    pub use crate::material::{Material, Lambertian, OrenNayar, Metal, Conductor, Dielectric, RoughDielectric, Principled, Coated, MixMaterial, DiffuseLight, Isotropic, HenyeyGreenstein};
+    pub use crate::spectrum::Ior;
+    pub use crate::medium::{Medium, MediumInterface, HomogeneousMedium, ChromaticMedium};
     pub use crate::constant_medium::constant_medium;
+    pub use crate::heterogeneous_medium::HeterogeneousMedium;
+    pub use crate::density::{DensityField, DensityGrid, NoiseDensity, TextureDensity};
     
     // Image textures
     pub use crate::image_loader::ImageTextureData;
@@ -0,0 +1,80 @@
+// Regression renders for participating media: tiny scene files with a known answer at the
+// center pixel.
+
+use std::fs;
+use std::path::Path;
+
+use rusty_raytracing::scene_file::parse_scene;
+
+// Center pixel (red channel) of a scene rendered from TOML source
+fn center_pixel(source: &str) -> f64 {
+    center_pixel_in(source, Path::new("."))
+}
+
+fn center_pixel_in(source: &str, base_dir: &Path) -> f64 {
+    let mut scene = parse_scene(source, base_dir).expect("scene should parse");
+    let image = scene.render();
+    image.get(image.width() / 2, image.height() / 2).x()
+}
+
+const CAMERA: &str = r#"
+[camera]
+aspect_ratio = 1.0
+image_width = 21
+samples_per_pixel = 64
+max_depth = 20
+vfov = 20
+look_from = [0, 0, 10]
+look_at = [0, 0, 0]
+background = [1, 1, 1]
+"#;
+
+// A glass sphere and a black smoke medium sharing the same sphere as boundary: the smoke must
+// still be entered through the glass. About exp(-6) gets through, plus the ~4% the glass reflects
+#[test]
+fn smoke_inside_coincident_glass() {
+    let source = format!("{}{}", CAMERA, r#"
+[materials.glass]
+type = "dielectric"
+refraction_index = 1.5
+
+[[objects]]
+type = "sphere"
+center = [0, 0, 0]
+radius = 1
+material = "glass"
+
+[[objects]]
+type = "constant_medium"
+density = 3
+albedo = [0, 0, 0]
+boundary = { type = "sphere", center = [0, 0, 0], radius = 1 }
+"#);
+    let center = center_pixel(&source);
+    assert!(center < 0.2, "smoke behind the glass was skipped: center pixel {}", center);
+}
+
+// A dense grid medium moved into place with a transform takes its density along: looking at
+// where the box ended up has to show the black medium, not the background behind it
+#[test]
+fn transformed_grid_medium() {
+    let dir = std::env::temp_dir().join(format!("rusty_raytracing_grid_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let ones: Vec<u8> = (0..8).flat_map(|_| 1.0f32.to_le_bytes()).collect();
+    fs::write(dir.join("ones.raw"), ones).unwrap();
+
+    let source = CAMERA.replace("look_from = [0, 0, 10]", "look_from = [3, 0, 10]").replace("look_at = [0, 0, 0]", "look_at = [3, 0, 0]")
+        + r#"
+[[objects]]
+type = "heterogeneous_medium"
+density = 5
+grid = "ones.raw"
+resolution = [2, 2, 2]
+albedo = [0, 0, 0]
+boundary = { type = "box", a = [-1, -1, -1], b = [1, 1, 1] }
+transform = [{ rotate_y = 30 }, { translate = [3, 0, 0] }]
+"#;
+    let center = center_pixel_in(&source, &dir);
+    fs::remove_dir_all(&dir).ok();
+    assert!(center < 0.05, "the grid stayed behind at the origin: center pixel {}", center);
+}